@@ -0,0 +1,206 @@
+//! 分类树构建、对比以及加解密的基准测试
+//!
+//! 使用合成的分类结果，不依赖任何真实的行业数据
+
+use std::{fs, path::PathBuf};
+
+use calamine::{Data, Range};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sisyphus::classi::{build_tree_from_range, crypto, ClassiTree, ParseConfig};
+
+/// 字段数量
+const SIZES: [usize; 3] = [1_000, 5_000, 10_000];
+/// 分类级别数量
+const DEPTHS: [usize; 2] = [2, 4];
+/// 每个分类节点下的子分类数量
+const FANOUT: usize = 4;
+/// 最末级分类下的字段数量
+const FIELDS_PER_CLASS: usize = 20;
+/// 加解密的明文大小
+const PAYLOADS: [usize; 2] = [1 << 20, 16 << 20];
+/// 批量加密的文件数量
+const BATCH_FILES: usize = 200;
+/// 批量加密时每个文件的大小
+const BATCH_FILE_SIZE: usize = 256 << 10;
+
+/// 生成合成的分类结果表格，第0行为表头
+///
+/// 同一级别的分类名称互不相同，上级分类由下级分类的编号整除`FANOUT`得到。
+/// `miss_every`不为0时，每隔`miss_every`行修改一次字段名称，模拟分类结果中缺失的字段
+fn synthetic_range(fields: usize, depth: usize, miss_every: usize) -> Range<Data> {
+    let width = depth as u32 + 3;
+    let mut range = Range::new((0, 0), (fields as u32, width - 1));
+
+    for level in 0..depth as u32 {
+        range.set_value((0, level), Data::String(format!("{}级分类", level + 1)));
+    }
+    range.set_value((0, width - 3), Data::String(String::from("数据库名称")));
+    range.set_value((0, width - 2), Data::String(String::from("表名称")));
+    range.set_value((0, width - 1), Data::String(String::from("字段名称")));
+
+    for i in 0..fields {
+        let row = i as u32 + 1;
+        let mut class_id = i / FIELDS_PER_CLASS;
+        for level in (0..depth as u32).rev() {
+            range.set_value(
+                (row, level),
+                Data::String(format!("L{}-{}", level, class_id)),
+            );
+            class_id /= FANOUT;
+        }
+
+        let field = if miss_every != 0 && i % miss_every == 0 {
+            format!("missing_{}", i)
+        } else {
+            format!("field_{}", i)
+        };
+        range.set_value((row, width - 3), Data::String(format!("db_{}", i % 4)));
+        range.set_value((row, width - 2), Data::String(format!("tb_{}", i / 100)));
+        range.set_value((row, width - 1), Data::String(field));
+    }
+
+    range
+}
+
+fn build_tree(fields: usize, depth: usize, miss_every: usize) -> ClassiTree {
+    build_tree_from_range(
+        &synthetic_range(fields, depth, miss_every),
+        &ParseConfig::default(),
+    )
+    .expect("synthetic range must build")
+}
+
+fn bench_build_tree(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build_tree_from_range");
+    for depth in DEPTHS {
+        for fields in SIZES {
+            let range = synthetic_range(fields, depth, 0);
+            let config = ParseConfig::default();
+            group.throughput(Throughput::Elements(fields as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("depth{}", depth), fields),
+                &range,
+                |b, range| b.iter(|| build_tree_from_range(black_box(range), &config)),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_diff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("diff");
+    for depth in DEPTHS {
+        for fields in SIZES {
+            let solution = build_tree(fields, depth, 0);
+            let answer = build_tree(fields, depth, 10);
+            group.throughput(Throughput::Elements(fields as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("depth{}", depth), fields),
+                &(solution, answer),
+                |b, (solution, answer)| b.iter(|| black_box(solution).diff(black_box(answer))),
+            );
+        }
+    }
+    group.finish();
+}
+
+/// 标准答案和分类结果完全相同时的对比，会走摘要相同的快速路径
+fn bench_diff_identical(c: &mut Criterion) {
+    let mut group = c.benchmark_group("diff_identical");
+    for depth in DEPTHS {
+        for fields in SIZES {
+            let solution = build_tree(fields, depth, 0);
+            let answer = build_tree(fields, depth, 0);
+            group.throughput(Throughput::Elements(fields as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("depth{}", depth), fields),
+                &(solution, answer),
+                |b, (solution, answer)| b.iter(|| black_box(solution).diff(black_box(answer))),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_all_leaves(c: &mut Criterion) {
+    let mut group = c.benchmark_group("all_leaves");
+    for depth in DEPTHS {
+        for fields in SIZES {
+            let tree = build_tree(fields, depth, 0);
+            group.throughput(Throughput::Elements(fields as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("depth{}", depth), fields),
+                &tree,
+                |b, tree| b.iter(|| black_box(tree).all_leaves().len()),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn bench_crypto(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crypto");
+    let key = crypto::EncKey::built_in();
+    for size in PAYLOADS {
+        let plain: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        let enc = crypto::encrypt_bytes(&plain, &key).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("encrypt", size), &plain, |b, plain| {
+            b.iter(|| crypto::encrypt_bytes(black_box(plain), &key).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("decrypt", size), &enc, |b, enc| {
+            b.iter(|| crypto::decrypt_bytes(black_box(enc), &key).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// 批量加密`BATCH_FILES`个文件，对比单线程和使用全部CPU核
+fn bench_encrypt_batch(c: &mut Criterion) {
+    let dir = std::env::temp_dir().join(format!("sisyphus-bench-batch-{}", std::process::id()));
+    let out_dir = dir.join("out");
+    fs::create_dir_all(&dir).unwrap();
+    let files: Vec<PathBuf> = (0..BATCH_FILES)
+        .map(|i| {
+            let file = dir.join(format!("answer_{}.xlsx", i));
+            let plain: Vec<u8> = (0..BATCH_FILE_SIZE)
+                .map(|j| ((i + j) % 251) as u8)
+                .collect();
+            fs::write(&file, plain).unwrap();
+            file
+        })
+        .collect();
+    let key = crypto::EncKey::built_in();
+
+    let mut group = c.benchmark_group("encrypt_batch");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes((BATCH_FILES * BATCH_FILE_SIZE) as u64));
+    for jobs in [1, 0] {
+        group.bench_with_input(BenchmarkId::new("jobs", jobs), &jobs, |b, &jobs| {
+            b.iter(|| {
+                crypto::encrypt_batch(
+                    black_box(&files),
+                    &out_dir,
+                    jobs,
+                    crypto::Compression::None,
+                    &key,
+                    |_, _| (),
+                )
+                .unwrap()
+            })
+        });
+    }
+    group.finish();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+criterion_group!(
+    benches,
+    bench_build_tree,
+    bench_diff,
+    bench_diff_identical,
+    bench_all_leaves,
+    bench_crypto,
+    bench_encrypt_batch
+);
+criterion_main!(benches);
@@ -0,0 +1,105 @@
+//! 解密加密文件时堆内存的峰值，对比先读入整个文件再解密和通过内存映射解密两种方式
+//!
+//! 通过统计分配的全局分配器测量，输出每种明文大小下两种方式的峰值，不计时。
+//! 先读入文件时峰值约为密文加明文的大小，内存映射时密文不占用堆内存，峰值约为明文的大小
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fs,
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use sisyphus::classi::crypto::{self, EncKey};
+
+/// 明文大小
+const PAYLOADS: [usize; 3] = [1 << 20, 16 << 20, 64 << 20];
+
+/// 当前已分配的堆内存
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+/// 上次重置以来已分配的堆内存的最大值
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// 在系统分配器之上统计已分配的字节数及其峰值
+struct PeakAlloc;
+
+impl PeakAlloc {
+    fn grow(size: usize) {
+        let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(current, Ordering::Relaxed);
+    }
+
+    fn shrink(size: usize) {
+        CURRENT.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            PeakAlloc::grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        PeakAlloc::shrink(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            if new_size > layout.size() {
+                PeakAlloc::grow(new_size - layout.size());
+            } else {
+                PeakAlloc::shrink(layout.size() - new_size);
+            }
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAlloc = PeakAlloc;
+
+/// 执行`f`期间堆内存的峰值，不包括执行前已经分配的内存
+fn peak_heap<T>(f: impl FnOnce() -> T) -> usize {
+    let base = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    black_box(f());
+    PEAK.load(Ordering::Relaxed) - base
+}
+
+fn mib(bytes: usize) -> f64 {
+    bytes as f64 / (1 << 20) as f64
+}
+
+fn main() {
+    let dir = std::env::temp_dir().join(format!("sisyphus-decrypt-memory-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let key = EncKey::built_in();
+
+    println!("{:>10}  {:>14}  {:>14}", "plaintext", "read_to_end", "mmap");
+    for size in PAYLOADS {
+        let enc_file = dir.join(format!("payload_{}", size));
+        let plain: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        fs::write(&enc_file, crypto::encrypt_bytes(&plain, &key).unwrap()).unwrap();
+        drop(plain);
+
+        let read = peak_heap(|| {
+            let enc = fs::read(&enc_file).unwrap();
+            crypto::decrypt_bytes(&enc, &key).unwrap()
+        });
+        let mapped = peak_heap(|| crypto::decrypt_file(&enc_file, &key).unwrap());
+        println!(
+            "{:>6.0} MiB  {:>10.1} MiB  {:>10.1} MiB",
+            mib(size),
+            mib(read),
+            mib(mapped)
+        );
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
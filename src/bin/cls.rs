@@ -26,533 +26,950 @@
 //! 4. 远程执行命令，解压压缩包并且验证结果文件
 //!
 //! 探针功能
-//! 1. cls -a <分类结果.xlsx>，对比标准答案，生成分类成绩，即总的正确率以及在各大类下的正确率
+//! 1. cls -a <分类结果.xlsx>，对比标准答案，生成分类成绩，即总的正确率以及在各大类下的正确率，
+//!    多次指定-a时合并多个分类结果文件，-a -时从标准输入读取分类结果，需要用--input-format指定格式
 //! 2. cls -e <分类结果.xlsx>，将分类结果加密，生成加密文件enc
+//! 3. cls -a <分类结果.xlsx> --no-header --class-levels <N>，分类结果文件没有表头时，
+//!    指定数据库列之前的分类级别数量，从第一行开始读取数据
+//! 4. cls trend -a <分类结果.xlsx=日期> -a ...，对多个不同时间的分类结果打分，输出各大类正确率的时间序列
+//! 5. cls encrypt-dir <目录> -j <N>，并行加密目录下的所有分类结果文件
+//! 6. cls report <对比结果.jsonl> --group-by table，不重新对比，按新的分组方式汇总保存的对比结果
+//! 7. cls normalize <分类结果.xlsx> -o <规范化.xlsx>，规范化分类结果文件，去掉空行和重复的字段并排序
+//! 8. cls key-info，输出当前密钥的指纹和来源，确认两个程序是否使用同一个密钥。密钥依次来自--key-file指定的文件、
+//!    SISYPHUS_KEY环境变量（十六进制），都没有时使用内置的密钥
+//! 9. cls --validate [加密文件]，检查加密的标准答案能否解密、打开并读取为分类树，用于部署后的远程验证
+//! 10. cls serve --bind <地址>，启动HTTP服务，为上传的分类结果打分，需要开启serve特性
+//! 11. cls churn <旧版本.xlsx> <新版本.xlsx>，统计同一份分类结果两个版本之间新增、删除和改变分类的字段数量
+//! 12. cls merge-reports <成绩1.json> <成绩2.json> ...，合并多次打分的JSON成绩，重新计算总的正确率
+
+#[cfg(feature = "tui")]
+use std::io::IsTerminal;
+use std::{collections::BTreeMap, fs, io, path::PathBuf, process};
 
-use std::{
-    cmp::Ordering,
-    collections::{HashMap, HashSet},
-    error::Error,
-    fmt::Display,
-    fs,
-    io::{BufReader, Cursor, Read, Write},
-    path::PathBuf,
-};
-
-use aes_gcm::{
-    aead::{Aead, OsRng},
-    AeadCore, Aes256Gcm, Key, KeyInit,
-};
 use anyhow::Context;
-use calamine::{open_workbook, open_workbook_from_rs, DataType, Reader, Xlsx};
-use clap::{arg, value_parser, Command};
-use serde::{ser::SerializeTupleStruct, Serialize};
+use clap::{arg, value_parser, ArgAction, Command};
+use indicatif::ProgressBar;
+use rand::{rngs::StdRng, SeedableRng};
+use sisyphus::classi::{
+    answer_churn, build_tree_from_range,
+    cache::load_or_build,
+    claussi_report,
+    crypto::{encrypt_batch, Compression, EncKey, FormatVersion, KeySource, KEY_ENV},
+    detect_swaps, encrypt_file, explain_misses, load_diff_result, mark_partial_paths,
+    near_duplicates, phantom_fields, print_depth_percentiles, print_grouped_report,
+    print_missing_classes, print_sampled_accuracy, read_classi_result, read_classi_results,
+    read_classi_sheet, read_schema, read_template_levels, render_report_template,
+    rollup_misses_by_table, sample_diff,
+    text::terminal_width,
+    to_format,
+    trend::{parse_dated_answer, trend_report},
+    validate_encrypted, write_json_lines,
+    writer::{count_changes, tree_table, write_table},
+    DiffResult, DiffUnit, DuplicateLog, FieldNormalize, GroupBy, MatchScope, ParseConfig,
+    ReportColumns, ScoringReport,
+};
 
 const ENC_FILE_PATH: &str = "./fix_e";
-const ENC_KEY: &[u8; 32] = &[
-    232, 222, 212, 202, 166, 177, 188, 199, 87, 34, 44, 10, 102, 1, 9, 0, 32, 22, 22, 20, 136, 177,
-    128, 199, 87, 32, 44, 10, 102, 2, 4, 6,
-];
-const CLASSI_SHEET: &str = "Sheet 1";
-const NONCE_LEN: usize = 96 / 8;
 
-#[derive(Serialize, Debug, Default)]
-struct DiffUnit {
-    classis: Vec<String>,
-    field: String,
-    field_exist: bool,
-}
-
-type DiffResult = Vec<DiffUnit>;
+fn main() -> anyhow::Result<()> {
+    let matches = Command::new("cls_profiler")
+        .about("数据分类探针")
+        .version("1.0.0")
+        .args([
+            arg!(answer: -a --answer <FILE> "指定分类结果文件的路径，可以指定多个，合并后打分")
+                .value_parser(value_parser!(PathBuf))
+                .action(ArgAction::Append),
+            arg!(dedupe: --dedupe "同一分类结果文件中重复的字段只保留第一次出现的行并警告，不报错"),
+            arg!(dedupe_report: --"dedupe-report" <FILE> "将去重时丢弃的字段及其保留和丢弃的位置输出为CSV文件")
+                .value_parser(value_parser!(PathBuf)),
+            arg!(strict_duplicates: --"strict-duplicates-across-files" <BOOL> "多个分类结果文件中出现同一字段时报错，为false时保留第一次出现的字段并警告")
+                .value_parser(value_parser!(bool))
+                .default_value("true"),
+            arg!(encrypt: -e --encrypt <FILE> "指定要加密的分类结果文件的路径")
+                .value_parser(value_parser!(PathBuf)),
+            arg!(compress: --compress <CODEC> "加密前压缩分类结果文件的方式，旧版本的程序只能解密none")
+                .value_parser(["gzip", "zstd", "none"])
+                .default_value("none"),
+            arg!(no_header: --"no-header" "分类结果文件没有表头，第一行即为数据")
+                .requires("class_levels"),
+            arg!(class_levels: --"class-levels" <N> "无表头时，数据库列之前的分类级别数量")
+                .value_parser(value_parser!(usize)),
+            arg!(tolerant_header: --"tolerant-header" "表头只需包含要查找的名称即可识别，如数据库名称(必填)，也适用于表名、字段名以及和模版、--require-headers比较的分类列"),
+            arg!(sheet: --sheet <NAME> "分类结果所在的工作表，指定多个时并行读取后合并")
+                .action(ArgAction::Append)
+                .default_value("Sheet 1"),
+            arg!(auto_sheet: --"auto-sheet" "只指定了一个工作表并且该工作表读取失败或者没有数据时，尝试工作簿中的其他工作表"),
+            arg!(sheet_jobs: --"sheet-jobs" <N> "并行读取多个工作表的线程数量，0表示使用CPU核数")
+                .value_parser(value_parser!(usize))
+                .default_value("0"),
+            arg!(db_header: --"db-header" <NAME> "数据库列的表头名称，可以指定多个")
+                .action(ArgAction::Append),
+            arg!(min_levels: --"min-levels" <N> "分类级别数量的下限，低于时报错")
+                .value_parser(value_parser!(usize))
+                .default_value("1"),
+            arg!(max_depth: --"max-depth" <N> "分类级别数量的上限，超过时报错")
+                .value_parser(value_parser!(usize))
+                .default_value("12"),
+            arg!(require_headers: --"require-headers" <LIST> "逗号分隔的表头，忽略各项前后的空白和空项，分类结果的表头必须按顺序完全相同，否则在解析前报错"),
+            arg!(strict_unicode: --"strict-unicode" "分类或者字段中有控制字符、零宽空格等不可见字符时报错，否则只给出警告"),
+            arg!(split_fields: --"split-fields" <SEP> "字段列的单元格包含多个以SEP分隔的字段时，拆分为同一分类路径下的多个字段"),
+            arg!(map_db: --"map-db" <MAPPING> "将数据库名从from改为to，形式为from=to，可以指定多个，同时应用于标准答案和分类结果，只影响数据库名")
+                .action(ArgAction::Append),
+            arg!(include_field_in_path: --"include-field-in-path" "同一字段可以出现在不同的分类路径下，各自作为独立的字段对比"),
+            arg!(blank_row: --"blank-row" <MODE> "遇到空行时的处理方式，stop表示结束读取，skip表示跳过空行继续读取")
+                .value_parser(["stop", "skip"])
+                .default_value("skip"),
+            arg!(input_format: --"input-format" <FORMAT> "分类结果文件的格式，默认根据扩展名判断，从标准输入读取（-a -）时必须指定")
+                .value_parser(["xlsx", "csv"]),
+            arg!(col_offset: --"col-offset" <N> "跳过A列开始的N列，默认自动跳过左侧完全为空的列")
+                .value_parser(value_parser!(usize)),
+            arg!(header_columns: --"header-columns" <M> "在表头的前M列中查找数据库列，字段列之后的列全部忽略")
+                .value_parser(value_parser!(usize))
+                .default_value("64"),
+            arg!(template: --template <FILE> "分类结果模版，分类列的顺序和模版不同时按模版重新排列，名称不同时报错")
+                .value_parser(value_parser!(PathBuf)),
+            arg!(context_lines: --"context-lines" <N> "读取出错或者跳过行时，显示该行以及前后各N行的原始内容")
+                .value_parser(value_parser!(usize)),
+            arg!(lenient: --lenient "单元格中的Excel错误值（如#N/A）视为空值，不跳过所在的行；跳过数据库名、表名或字段名为空的行，不报错；缺列的行总是被跳过"),
+            arg!(verbose: -v --verbose "输出详细信息"),
+            arg!(validate: --validate [FILE] "端到端检查加密的标准答案能否解密并读取为分类树，逐个阶段输出结果，失败时退出码非0")
+                .value_parser(value_parser!(PathBuf))
+                .num_args(0..=1)
+                .default_missing_value(ENC_FILE_PATH),
+            arg!(format_version: --"format-version" <VERSION> "标准答案加密文件的格式版本，0为没有文件头的旧格式")
+                .value_parser(["auto", "0", "1", "2"])
+                .default_value("auto")
+                .global(true),
+            arg!(fm_normalize: --"fm-normalize" <LIST> "数据库名、表名、字段名的规范化方式，逗号分隔的trim、lower、collapse、unquote、nfc、invisible")
+                .default_value("")
+                .global(true),
+            arg!(rebuild_cache: --"rebuild-cache" "忽略标准答案的缓存，重新解密读取并生成缓存")
+                .global(true),
+            arg!(key_file: --"key-file" <PATH> "加解密使用的密钥文件，内容为32字节的原始密钥，优先于SISYPHUS_KEY环境变量")
+                .value_parser(value_parser!(PathBuf))
+                .global(true),
+            arg!(reconcile: --reconcile "只输出标准答案和分类结果各自独有的字段，不计算正确率"),
+            arg!(reconcile_csv: --"reconcile-csv" <FILE> "将字段对账结果输出为CSV文件")
+                .value_parser(value_parser!(PathBuf))
+                .requires("reconcile"),
+            arg!(taxonomy_diff: --"taxonomy-diff" "只对比标准答案和分类结果的分类体系结构，不考虑字段"),
+            arg!(taxonomy_patch: --"taxonomy-patch" "以统一差异格式输出分类体系结构的对比结果，删除的分类路径以-开头，新增的以+开头"),
+            arg!(json_lines: --"json-lines" "每个字段的对比结果输出为一行JSON，边对比边输出"),
+            arg!(report_fields: --"report-fields" <LIST> "逐字段输出时的列及其顺序，逗号分隔的db、table、field、path、actual_path、status、match_kind、missing_level"),
+            arg!(fields_csv: --"fields-csv" <FILE> "将每个字段的对比结果输出为CSV文件，列由--report-fields指定")
+                .value_parser(value_parser!(PathBuf)),
+            arg!(report_template: --"report-template" <FILE> "使用tinytemplate模版文件输出分类成绩")
+                .value_parser(value_parser!(PathBuf)),
+            arg!(only_class: --"only-class" <PATH> "只对指定分类路径下的字段打分，如个人信息/标识信息"),
+            arg!(warn_near_dupes: --"warn-near-dupes" [LIST] "读取后检查分类结果中规范化后相同、原始形式不同的字段，规范化方式同--fm-normalize")
+                .num_args(0..=1)
+                .default_missing_value("trim,lower"),
+            arg!(schema: --schema <FILE> "实际的数据库结构，每行一个数据库.表.字段或者CSV文件，列出分类结果中不存在的字段")
+                .value_parser(value_parser!(PathBuf)),
+            arg!(check_hierarchy: --"check-hierarchy" "检查同名分类是否出现在不同的上级分类下"),
+            arg!(count_only: --"count-only" "只输出总的正确率，如0.9234"),
+            arg!(min_accuracy: --"min-accuracy" <RATIO> "总的正确率低于该值时以非0状态码退出，取值0到1")
+                .value_parser(value_parser!(f64)),
+            arg!(average: --average <AVERAGE> "总的正确率的计算方式，micro为所有字段合在一起计算，macro为各大类正确率的平均值")
+                .value_parser(["micro", "macro"])
+                .default_value("micro"),
+            arg!(explain_report: --"explain-report" [N] "在成绩中给出每个正确率的计算依据，并在每个大类后列出最多N个未匹配的字段")
+                .value_parser(value_parser!(usize))
+                .num_args(0..=1)
+                .default_missing_value("3"),
+            arg!(allow_partial_paths: --"allow-partial-paths" "分类结果把字段分到了标准答案分类路径的上级分类下时，单独统计为部分匹配，仍不算匹配"),
+            arg!(group_accuracy_threshold: --"group-accuracy-threshold" <RATIO> "正确率低于该值的大类以!标记，并在成绩最后单独列出，取值0到1")
+                .value_parser(value_parser!(f64)),
+            arg!(fail_on_class_below: --"fail-on-class-below" "有大类的正确率低于--group-accuracy-threshold时以非0状态码退出")
+                .requires("group_accuracy_threshold"),
+            arg!(fail_on_extra: --"fail-on-extra" "分类结果中有标准答案中没有的字段时列出这些字段，并以非0状态码退出"),
+            arg!(rollup: --rollup <LEVEL> "未匹配字段的汇总方式，table表示按表汇总，否则在-v时逐个输出")
+                .value_parser(["field", "table"])
+                .default_value("field"),
+            arg!(detect_swaps: --"detect-swaps" "检查未匹配的字段是否因为表名和字段名两列填反"),
+            arg!(only_missing_classes: --"only-missing-classes" "只列出完全没有匹配字段的分类以及各自的字段数量，不包含没有字段的分类"),
+            arg!(quiet_empty_classes: --"quiet-empty-classes" "不输出标准答案中没有任何字段的大类（0/0）"),
+            arg!(group_depth: --"group-depth" <N> "列出完全没有匹配字段的分类时使用的分类级数，1表示大类")
+                .value_parser(value_parser!(usize))
+                .default_value("1"),
+            arg!(format: --format <FORMAT> "分类成绩和分类树的输出格式，yaml需要开启yaml特性")
+                .value_parser([
+                    "text",
+                    "json",
+                    #[cfg(feature = "yaml")]
+                    "yaml",
+                ])
+                .default_value("text"),
+            arg!(dump_tree: --"dump-tree" "只按--format输出分类结果的分类树，不打分"),
+            arg!(intersection: --intersection "只对两边都有的字段打分，衡量分类的一致性而不是覆盖率"),
+            arg!(match_scope: --"match-scope" <SCOPE> "字段的匹配范围，db-table时相同分类路径下有同一张表即算匹配，不要求字段名相同")
+                .value_parser(["db-table-field", "db-table", "db"])
+                .default_value("db-table-field"),
+            arg!(unordered_levels: --"unordered-levels" "（实验性）忽略分类级别的顺序，各级分类组成的集合相同即算匹配"),
+            arg!(export_matched: --"export-matched" <FILE> "将匹配的字段及其分类路径输出为CSV文件，列为db、table、field、path")
+                .value_parser(value_parser!(PathBuf)),
+            arg!(report_xlsx: --"report-xlsx" <FILE> "将成绩汇总、各大类成绩和逐字段对比结果输出到同一个.xlsx文件的三个工作表，逐字段的列由--report-fields指定")
+                .value_parser(value_parser!(PathBuf)),
+            // 不指定时保持原来每个大类一行正确率的输出，兼容解析这种输出的脚本
+            arg!(report_width: --"report-width" <N> "按该宽度排版文本成绩报告，各大类带有正确率的进度条，auto表示终端宽度（无法获取时为80）；不指定时每个大类输出一行正确率")
+                .value_parser(|width: &str| match width {
+                    "auto" => Ok(terminal_width()),
+                    _ => width
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&width| width > 0)
+                        .ok_or_else(|| format!("expected a positive width or auto, found [{}]", width)),
+                }),
+            arg!(output: -o --output <FILE> "将逐字段的对比结果和成绩汇总写入文件，格式由--output-format指定，不影响标准输出")
+                .value_parser(value_parser!(PathBuf)),
+            arg!(output_format: --"output-format" <FORMAT> "--output的文件格式，默认根据扩展名判断，.csv为csv，其他为json")
+                .value_parser(["json", "csv"]),
+            arg!(summary_csv: --"summary-csv" <FILE> "将各大类的成绩汇总输出为CSV文件，列为class、total、matched、accuracy")
+                .value_parser(value_parser!(PathBuf)),
+            arg!(depth_percentiles: --"depth-percentiles" "输出每个字段匹配的分类级数的p50、p90、p99"),
+            arg!(sample: --sample <N> "只随机抽取N个标准答案字段打分，并输出正确率的置信区间")
+                .value_parser(value_parser!(usize)),
+            arg!(seed: --seed <N> "随机数种子，目前只用于--sample的抽样，相同的输入和种子得到完全相同的输出；不影响加密使用的nonce")
+                .value_parser(value_parser!(u64)),
+            arg!(confidence: --confidence <LEVEL> "抽样打分时置信区间的置信水平，取值0到1")
+                .value_parser(value_parser!(f64))
+                .default_value("0.95"),
+            #[cfg(feature = "tui")]
+            arg!(tui: --tui "在终端界面中浏览各大类的正确率和未匹配的字段"),
+        ])
+        .subcommand(
+            Command::new("trend")
+                .about("对多个不同时间的分类结果打分，输出正确率的时间序列")
+                .args([
+                    arg!(answer: -a --answer <ANSWER> "分类结果文件，形式为file=2024-06-01，省略日期时从文件名推断")
+                        .action(ArgAction::Append)
+                        .required(true),
+                    arg!(format: --format <FORMAT> "时间序列的输出格式")
+                        .value_parser(["csv", "json"])
+                        .default_value("csv"),
+                ]),
+        )
+        .subcommand(
+            Command::new("encrypt-dir")
+                .about("并行加密目录下的所有分类结果文件")
+                .args([
+                    arg!(dir: <DIR> "分类结果文件所在的目录")
+                        .value_parser(value_parser!(PathBuf)),
+                    arg!(out: -o --out <DIR> "加密文件的输出目录")
+                        .value_parser(value_parser!(PathBuf))
+                        .default_value("./enc"),
+                    arg!(jobs: -j --jobs <N> "并行加密的线程数量，0表示使用CPU核数")
+                        .value_parser(value_parser!(usize))
+                        .default_value("0"),
+                    arg!(compress: --compress <CODEC> "加密前压缩分类结果文件的方式，旧版本的程序只能解密none")
+                        .value_parser(["gzip", "zstd", "none"])
+                        .default_value("none"),
+                ]),
+        )
+        .subcommand(
+            Command::new("report")
+                .about("不重新对比，重新汇总保存的对比结果")
+                .args([
+                    arg!(diff: <FILE> "保存的对比结果，支持JSON、JSON Lines和CSV")
+                        .value_parser(value_parser!(PathBuf)),
+                    arg!(group_by: --"group-by" <GROUP> "分组方式，class、class:N（前N级分类）、database或者table")
+                        .default_value("class"),
+                    arg!(top_n: --"top-n" <N> "只输出正确率最低的N个分组")
+                        .value_parser(value_parser!(usize)),
+                    arg!(average: --average <AVERAGE> "总的正确率的计算方式，micro为所有字段合在一起计算，macro为各分组正确率的平均值")
+                        .value_parser(["micro", "macro"])
+                        .default_value("micro"),
+                    arg!(format: --format <FORMAT> "汇总结果的输出格式")
+                        .value_parser(["text", "json"])
+                        .default_value("text"),
+                ]),
+        )
+        .subcommand(
+            Command::new("normalize")
+                .about("规范化分类结果文件，输出整理后的.xlsx或者.csv文件")
+                .args([
+                    arg!(file: <FILE> "要规范化的分类结果文件")
+                        .value_parser(value_parser!(PathBuf)),
+                    arg!(out: -o --out <FILE> "输出文件，扩展名为.xlsx或者.csv")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true),
+                    arg!(sheet: --sheet <NAME> "分类结果所在的工作表").default_value("Sheet 1"),
+                ]),
+        )
+        .subcommand({
+            let serve = Command::new("serve")
+                .about("启动HTTP服务，为POST /score上传的分类结果打分，返回JSON格式的成绩，需要开启serve特性")
+                .args([
+                    arg!(bind: --bind <ADDR> "监听的地址").default_value("127.0.0.1:8080"),
+                    arg!(solution: --solution <FILE> "加密的标准答案文件，启动时读取一次")
+                        .value_parser(value_parser!(PathBuf))
+                        .default_value(ENC_FILE_PATH),
+                    arg!(sheet: --sheet <NAME> "分类结果所在的工作表").default_value("Sheet 1"),
+                ]);
+            #[cfg(not(feature = "serve"))]
+            let serve = serve.hide(true);
+            serve
+        })
+        .subcommand(
+            Command::new("churn")
+                .about("统计同一份分类结果两个版本之间的变动，不涉及标准答案")
+                .args([
+                    arg!(old: <OLD> "旧版本的分类结果文件")
+                        .value_parser(value_parser!(PathBuf)),
+                    arg!(new: <NEW> "新版本的分类结果文件")
+                        .value_parser(value_parser!(PathBuf)),
+                    arg!(sheet: --sheet <NAME> "分类结果所在的工作表").default_value("Sheet 1"),
+                    arg!(format: --format <FORMAT> "统计结果的输出格式")
+                        .value_parser(["text", "json"])
+                        .default_value("text"),
+                ]),
+        )
+        .subcommand(
+            Command::new("merge-reports")
+                .about("合并多次打分保存的JSON成绩（--format json的输出），重新计算总的正确率和各大类的正确率")
+                .args([
+                    arg!(reports: <REPORT> "JSON格式的成绩文件")
+                        .value_parser(value_parser!(PathBuf))
+                        .num_args(1..),
+                    arg!(by_source: --"by-source" "列出每个大类在各个成绩文件中的匹配数和总数"),
+                    arg!(format: --format <FORMAT> "合并后成绩的输出格式")
+                        .value_parser(["text", "json"])
+                        .default_value("text"),
+                ]),
+        )
+        .subcommand(Command::new("key-info").about("输出当前使用的密钥的指纹和来源，不输出密钥本身"))
+        .arg_required_else_help(true)
+        .get_matches();
 
-fn claussi_report(r: &DiffResult) -> anyhow::Result<()> {
-    let json_res = serde_json::to_string_pretty(&r)?;
+    // 全局参数在子命令之后指定时只出现在子命令的参数中
+    let key_file = match matches.subcommand() {
+        Some((_, sub)) => sub.get_one::<PathBuf>("key_file"),
+        None => matches.get_one::<PathBuf>("key_file"),
+    };
+    // 只在加密或解密时读取密钥，不用密钥的子命令不会因为密钥文件有误而失败
+    let load_key = |warn_built_in: bool| -> anyhow::Result<EncKey> {
+        let key = EncKey::resolve(key_file.map(PathBuf::as_path))
+            .context("failed to load the encryption key")?;
+        if warn_built_in && *key.source() == KeySource::BuiltIn {
+            eprintln!(
+                "warning: neither --key-file nor {} is set, using the built-in key",
+                KEY_ENV
+            );
+        }
+        Ok(key)
+    };
 
-    let total = r.len() as i32;
-    let mut match_classi = 0;
-    let mut group_statistic = HashMap::<String, (i32, i32)>::new();
-    for unit in r {
-        let first_classi = unit.classis[0].clone();
-        let cal_u = if unit.field_exist { 1 } else { 0 };
-        match_classi += cal_u;
-        group_statistic
-            .entry(first_classi)
-            .and_modify(|e| {
-                e.0 += 1;
-                e.1 += cal_u;
-            })
-            .or_insert((1, cal_u));
+    if let Some(("trend", sub)) = matches.subcommand() {
+        let answers = sub
+            .get_many::<String>("answer")
+            .unwrap()
+            .map(|spec| parse_dated_answer(spec))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let key = load_key(true)?;
+        let solution_file = PathBuf::from(ENC_FILE_PATH);
+        let normalize: FieldNormalize = sub.get_one::<String>("fm_normalize").unwrap().parse()?;
+        let solution_config = ParseConfig::builder()
+            .format_version(sub.get_one::<String>("format_version").unwrap().parse()?)
+            .key(key.clone())
+            .normalize(normalize)
+            .build();
+        let solution = load_or_build(
+            &solution_file,
+            &normalize.to_string(),
+            sub.get_flag("rebuild_cache"),
+            &key,
+            || Ok(read_classi_result(&solution_file, true, &solution_config)?),
+        )?;
+        let answer_config = ParseConfig::builder().normalize(normalize).build();
+        let format = sub.get_one::<String>("format").unwrap();
+        return trend_report(&solution, &answers, &answer_config, format);
     }
 
-    let ratio = match_classi as f64 / total as f64;
-    println!("total classification accuracy: {:.2}%", ratio * 100f64);
-
-    for (k, v) in group_statistic {
+    if let Some(("encrypt-dir", sub)) = matches.subcommand() {
+        let dir = sub.get_one::<PathBuf>("dir").unwrap();
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "xlsx") {
+                files.push(path);
+            }
+        }
+        files.sort();
+
+        let key = load_key(true)?;
+        let bar = ProgressBar::new(files.len() as u64);
+        let results = encrypt_batch(
+            &files,
+            sub.get_one::<PathBuf>("out").unwrap(),
+            *sub.get_one::<usize>("jobs").unwrap(),
+            sub.get_one::<String>("compress").unwrap().parse()?,
+            &key,
+            |_, _| bar.inc(1),
+        )?;
+        bar.finish_and_clear();
+
+        let mut failed = 0;
+        for (file, res) in files.iter().zip(&results) {
+            match res {
+                Ok(enc_file) => println!(
+                    "ok     {} -> {}",
+                    file.to_string_lossy(),
+                    enc_file.to_string_lossy()
+                ),
+                Err(e) => {
+                    failed += 1;
+                    println!("failed {}: {}", file.to_string_lossy(), e);
+                }
+            }
+        }
         println!(
-            "classification [{}] accuracy: {:.2}%",
-            k,
-            v.1 as f64 / v.0 as f64 * 100f64
+            "encrypted {} files, {} failed",
+            results.len() - failed,
+            failed
         );
+        return Ok(());
     }
 
-    Ok(())
-}
-
-#[derive(Debug)]
-struct ClassiError {
-    msg: &'static str,
-}
-
-impl ClassiError {
-    fn new(msg: &'static str) -> Self {
-        Self { msg }
+    if let Some(("serve", sub)) = matches.subcommand() {
+        #[cfg(feature = "serve")]
+        {
+            let solution = read_classi_result(
+                sub.get_one::<PathBuf>("solution").unwrap(),
+                true,
+                &ParseConfig::builder().key(load_key(true)?).build(),
+            )?;
+            let answer_config = ParseConfig::builder()
+                .sheet_name(sub.get_one::<String>("sheet").unwrap())
+                .build();
+            return sisyphus::classi::serve::run(
+                sub.get_one::<String>("bind").unwrap(),
+                &solution,
+                &answer_config,
+            );
+        }
+        #[cfg(not(feature = "serve"))]
+        {
+            let _ = sub;
+            return Err(anyhow::Error::msg(
+                "the serve subcommand requires building with the serve feature",
+            ));
+        }
     }
-}
 
-impl Display for ClassiError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "classification error: {}", self.msg)
+    if let Some(("churn", sub)) = matches.subcommand() {
+        let config = ParseConfig::builder()
+            .sheet_name(sub.get_one::<String>("sheet").unwrap())
+            .build();
+        let old = read_classi_result(sub.get_one::<PathBuf>("old").unwrap(), false, &config)?;
+        let new = read_classi_result(sub.get_one::<PathBuf>("new").unwrap(), false, &config)?;
+        let churn = answer_churn(&old, &new);
+        match sub.get_one::<String>("format").unwrap().as_str() {
+            "text" => churn.print(),
+            format => print!("{}", to_format(&churn, format)?),
+        }
+        return Ok(());
     }
-}
-
-impl Error for ClassiError {}
-
-type Database = String;
-type Table = String;
-type Field = String;
-
-#[derive(Debug, PartialEq, Eq, Clone, Default, Hash)]
-struct FieldMeta(Database, Table, Field);
 
-impl Display for FieldMeta {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}-{}-{}", self.0, self.1, self.2)
+    if let Some(("merge-reports", sub)) = matches.subcommand() {
+        let files: Vec<&PathBuf> = sub.get_many::<PathBuf>("reports").unwrap().collect();
+        let reports = files
+            .iter()
+            .map(|file| {
+                let content = fs::read_to_string(file)
+                    .with_context(|| format!("failed to read [{}]", file.to_string_lossy()))?;
+                serde_json::from_str::<ScoringReport>(&content)
+                    .with_context(|| format!("[{}] is not a JSON report", file.to_string_lossy()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let merged = ScoringReport::merge(&reports);
+        let format = sub.get_one::<String>("format").unwrap();
+        print_grouped_report(&merged, None, format)?;
+        if sub.get_flag("by_source") && format == "text" {
+            for class in &merged.per_class {
+                println!("class [{}]", class.class);
+                for (file, report) in files.iter().zip(&reports) {
+                    if let Some(c) = report.per_class.iter().find(|c| c.class == class.class) {
+                        println!("  {}: {}/{}", file.to_string_lossy(), c.matched, c.total);
+                    }
+                }
+            }
+        }
+        return Ok(());
     }
-}
 
-impl Serialize for FieldMeta {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        let mut ser = serializer.serialize_tuple_struct("field", 3)?;
-        ser.serialize_field(&self.0)?;
-        ser.serialize_field(&self.1)?;
-        ser.serialize_field(&self.2)?;
-        ser.end()
+    if let Some(("key-info", _)) = matches.subcommand() {
+        let key = load_key(false)?;
+        println!("fingerprint: {}", key.fingerprint());
+        println!("source: {}", key.source());
+        return Ok(());
     }
-}
-
-#[derive(Debug, PartialEq, Eq, Clone)]
-enum ClassiVal {
-    Root,
-    Classi(String),
-    Field(FieldMeta),
-}
 
-impl Display for ClassiVal {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ClassiVal::Root => write!(f, "root"),
-            ClassiVal::Classi(ref s) => write!(f, "classi({})", s),
-            ClassiVal::Field(ref dtf) => write!(f, "field({})", dtf),
-        }
+    if let Some(("report", sub)) = matches.subcommand() {
+        let diff = load_diff_result(sub.get_one::<PathBuf>("diff").unwrap())?;
+        let group_by: GroupBy = sub.get_one::<String>("group_by").unwrap().parse()?;
+        let mut report = ScoringReport::grouped(&diff, group_by);
+        report.set_average(sub.get_one::<String>("average").unwrap().parse()?);
+        return print_grouped_report(
+            &report,
+            sub.get_one::<usize>("top_n").copied(),
+            sub.get_one::<String>("format").unwrap(),
+        );
     }
-}
 
-struct ClassiNode {
-    val: ClassiVal,
-    subs: Option<Vec<ClassiNode>>,
-}
-
-impl From<&ClassiVal> for ClassiNode {
-    fn from(value: &ClassiVal) -> Self {
-        let val = value.clone();
-        Self { val, subs: None }
+    if let Some(("normalize", sub)) = matches.subcommand() {
+        let file = sub.get_one::<PathBuf>("file").unwrap();
+        let out = sub.get_one::<PathBuf>("out").unwrap();
+        let sheet_name = sub.get_one::<String>("sheet").unwrap();
+        let config = ParseConfig::builder()
+            .sheet_name(sheet_name)
+            .normalize("trim,collapse,unquote,nfc".parse()?)
+            .class_normalize("trim,collapse,nfc".parse()?)
+            .dedupe(true)
+            .build();
+        let sheet = read_classi_sheet(file, false, &config)?;
+        let table = tree_table(&build_tree_from_range(&sheet, &config)?);
+        write_table(&table, out, sheet_name)?;
+        let (changed, dropped) = count_changes(&sheet, &table);
+        println!(
+            "wrote {} rows to [{}]: {} changed, {} dropped",
+            table.len() - 1,
+            out.to_string_lossy(),
+            changed,
+            dropped
+        );
+        return Ok(());
     }
-}
 
-impl ClassiNode {
-    fn new(val: ClassiVal) -> Self {
-        Self { val, subs: None }
+    // 其余的加密、校验和打分都需要密钥
+    if matches.get_one::<PathBuf>("encrypt").is_none()
+        && matches.get_one::<PathBuf>("validate").is_none()
+        && matches.get_many::<PathBuf>("answer").is_none()
+    {
+        return Ok(());
     }
+    let key = load_key(true)?;
 
-    fn find_node(&self, val: &ClassiVal) -> Option<&ClassiNode> {
-        if self.val == *val {
-            Some(self)
-        } else {
-            match self.subs {
-                Some(ref subs) => {
-                    for sub_node in subs {
-                        if let Some(n) = sub_node.find_node(val) {
-                            return Some(n);
-                        }
-                    }
-                    None
-                }
-                None => None,
-            }
+    if let Some(ef) = matches.get_one::<PathBuf>("encrypt") {
+        let enc_file = PathBuf::from(ENC_FILE_PATH);
+        let compression: Compression = matches.get_one::<String>("compress").unwrap().parse()?;
+        encrypt_file(ef, &enc_file, compression, &key)?;
+        if compression != Compression::None {
+            let before = fs::metadata(ef)?.len();
+            let after = fs::metadata(&enc_file)?.len();
+            println!(
+                "compressed with {}: {} bytes -> {} bytes ({:.1}% smaller)",
+                compression,
+                before,
+                after,
+                (1.0 - after as f64 / before.max(1) as f64) * 100.0
+            );
         }
     }
 
-    fn add_node(&mut self, sup_val: &ClassiVal, val: &ClassiVal) -> Result<(), ClassiError> {
-        if self.val == *sup_val {
-            let t_node = ClassiNode::from(val);
-            match self.subs {
-                Some(ref mut subs) => {
-                    for e in subs.iter() {
-                        if e.val == *val {
-                            return Err(ClassiError::new("the node exists"));
-                        }
-                    }
-                    subs.push(t_node);
-                }
-                None => {
-                    let new_nodes = vec![t_node];
-                    self.subs = Some(new_nodes);
-                }
-            }
-            Ok(())
-        } else {
-            match self.subs {
-                Some(ref mut subs) => {
-                    let mut is_add = false;
-                    for e in subs {
-                        match e.add_node(sup_val, val) {
-                            Ok(_) => {
-                                is_add = true;
-                                break;
-                            }
-                            Err(e) => {
-                                if e.msg == "the node exists" {
-                                    return Err(e);
-                                } else {
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                    if !is_add {
-                        Err(ClassiError::new("the super node does not found"))
-                    } else {
-                        Ok(())
-                    }
-                }
-                None => Err(ClassiError::new("the super node does not found")),
+    if let Some(enc_file) = matches.get_one::<PathBuf>("validate") {
+        let config = ParseConfig::builder()
+            .format_version(
+                matches
+                    .get_one::<String>("format_version")
+                    .unwrap()
+                    .parse()?,
+            )
+            .key(key.clone())
+            .build();
+        let stages = validate_encrypted(enc_file, &config);
+        for stage in &stages {
+            match &stage.result {
+                Ok(detail) => println!("ok     {} {}", stage.name, detail),
+                Err(e) => println!("failed {}: {}", stage.name, e),
             }
         }
+        if stages.len() < 4 || stages.iter().any(|stage| stage.result.is_err()) {
+            process::exit(1);
+        }
+        return Ok(());
     }
 
-    fn to_string(&self, space: usize) -> String {
-        const INDENT: &str = "  ";
-        let mut res = String::new();
-        match self.val {
-            ClassiVal::Root => {
-                if let Some(sub) = &self.subs {
-                    for e in sub {
-                        res.push_str(&e.to_string(space));
-                    }
-                }
-            }
-            ClassiVal::Classi(ref inner) => {
-                res.push_str((INDENT.repeat(space) + inner.as_str() + "\n").as_str());
-                if let Some(sub) = &self.subs {
-                    for e in sub {
-                        res.push_str(&e.to_string(space + 1));
-                    }
+    if let Some(afs) = matches.get_many::<PathBuf>("answer") {
+        let afs: Vec<PathBuf> = afs.cloned().collect();
+        let solution_file = PathBuf::from(ENC_FILE_PATH);
+        let class_levels = if matches.get_flag("no_header") {
+            matches.get_one::<usize>("class_levels").copied()
+        } else {
+            None
+        };
+        let verbose = matches.get_flag("verbose");
+        let format_version: FormatVersion = matches
+            .get_one::<String>("format_version")
+            .unwrap()
+            .parse()?;
+        let normalize: FieldNormalize =
+            matches.get_one::<String>("fm_normalize").unwrap().parse()?;
+        let field_in_path = matches.get_flag("include_field_in_path");
+        // 重命名的两侧使用和数据库名相同的规范化方式，规范化后的数据库名才能匹配
+        let db_map = matches
+            .get_many::<String>("map_db")
+            .into_iter()
+            .flatten()
+            .map(|mapping| match mapping.split_once('=') {
+                Some((from, to)) if !from.is_empty() && !to.is_empty() => {
+                    Ok((normalize.apply(from), normalize.apply(to)))
                 }
-            }
-            ClassiVal::Field(ref dtf) => {
-                res.push_str((INDENT.repeat(space) + dtf.to_string().as_str() + "\n").as_str());
-            }
+                _ => Err(anyhow::Error::msg(format!(
+                    "invalid database mapping [{}], expected from=to",
+                    mapping
+                ))),
+            })
+            .collect::<anyhow::Result<BTreeMap<_, _>>>()?;
+        let solution_config = ParseConfig::builder()
+            .verbose(verbose)
+            .format_version(format_version)
+            .key(key.clone())
+            .normalize(normalize)
+            .db_map(db_map.clone())
+            .field_in_path(field_in_path)
+            .build();
+        let mut cache_options = if field_in_path {
+            format!("{};field-in-path", normalize)
+        } else {
+            normalize.to_string()
+        };
+        for (from, to) in &db_map {
+            cache_options.push_str(&format!(";map-db:{}={}", from, to));
         }
-
-        res
-    }
-}
-
-struct ClassiTree {
-    root: ClassiNode,
-}
-
-impl ClassiTree {
-    fn new() -> Self {
-        ClassiTree {
-            root: ClassiNode::new(ClassiVal::Root),
+        let solution = load_or_build(
+            &solution_file,
+            &cache_options,
+            matches.get_flag("rebuild_cache"),
+            &key,
+            || Ok(read_classi_result(&solution_file, true, &solution_config)?),
+        )?;
+        let mut answer_config = ParseConfig::builder()
+            .sheet_names(
+                matches
+                    .get_many::<String>("sheet")
+                    .unwrap()
+                    .cloned()
+                    .collect(),
+            )
+            .sheet_jobs(*matches.get_one::<usize>("sheet_jobs").unwrap())
+            .auto_sheet(matches.get_flag("auto_sheet"))
+            .class_levels(class_levels)
+            .tolerant_header(matches.get_flag("tolerant_header"))
+            .min_levels(*matches.get_one::<usize>("min_levels").unwrap())
+            .max_depth(*matches.get_one::<usize>("max_depth").unwrap())
+            .lenient(matches.get_flag("lenient"))
+            .context_lines(matches.get_one::<usize>("context_lines").copied())
+            .header_columns(*matches.get_one::<usize>("header_columns").unwrap())
+            .col_offset(matches.get_one::<usize>("col_offset").copied())
+            .blank_row(matches.get_one::<String>("blank_row").unwrap().parse()?)
+            .input_format(
+                matches
+                    .get_one::<String>("input_format")
+                    .map(|f| f.parse())
+                    .transpose()?,
+            )
+            .normalize(normalize)
+            .db_map(db_map)
+            .split_fields(matches.get_one::<String>("split_fields").cloned())
+            .strict_unicode(matches.get_flag("strict_unicode"))
+            .required_headers(matches.get_one::<String>("require_headers").map(|list| {
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|head| !head.is_empty())
+                    .map(String::from)
+                    .collect()
+            }))
+            .field_in_path(field_in_path)
+            .verbose(verbose);
+        if let Some(db_headers) = matches.get_many::<String>("db_header") {
+            answer_config = answer_config.db_headers(db_headers.cloned().collect());
         }
-    }
-
-    fn find_node(&self, val: &ClassiVal) -> Option<&ClassiNode> {
-        self.root.find_node(val)
-    }
-
-    fn add_node(&mut self, classis: &[&str], field: FieldMeta) -> Result<(), ClassiError> {
-        let l = classis.len();
-        match l.cmp(&1usize) {
-            Ordering::Greater => {
-                let _ = self.root.add_node(
-                    &ClassiVal::Root,
-                    &ClassiVal::Classi(String::from(classis[0])),
+        let dedupe_report = matches.get_one::<PathBuf>("dedupe_report");
+        let mut answer_config = answer_config
+            .dedupe(matches.get_flag("dedupe"))
+            .duplicate_log(dedupe_report.map(|_| DuplicateLog::default()))
+            .build();
+        if let Some(template) = matches.get_one::<PathBuf>("template") {
+            answer_config.template_levels = Some(read_template_levels(template, &answer_config)?);
+        }
+        let answer = read_classi_results(
+            &afs,
+            &answer_config,
+            *matches.get_one::<bool>("strict_duplicates").unwrap(),
+        )?;
+        if let (Some(file), Some(log)) = (dedupe_report, &answer_config.duplicate_log) {
+            let dropped = log.write_csv(file)?;
+            eprintln!(
+                "{} dropped duplicate fields written to [{}]",
+                dropped,
+                file.to_string_lossy()
+            );
+        }
+        if let Some(near) = matches.get_one::<String>("warn_near_dupes") {
+            let near: FieldNormalize = near.parse()?;
+            let pairs = near_duplicates(&answer, &near);
+            for (a, b) in &pairs {
+                eprintln!(
+                    "warning: fields [{}] and [{}] differ only in formatting",
+                    a, b
                 );
-
-                for win in classis.windows(2) {
-                    match self.root.add_node(
-                        &ClassiVal::Classi(String::from(win[0])),
-                        &ClassiVal::Classi(String::from(win[1])),
-                    ) {
-                        Ok(_) => continue,
-                        Err(e) => {
-                            if e.msg == "the node exists" {
-                                continue;
-                            } else {
-                                return Err(ClassiError::new("failed to add classification level"));
-                            }
-                        }
-                    }
-                }
-
-                self.root.add_node(
-                    &ClassiVal::Classi(String::from(classis[classis.len() - 1])),
-                    &ClassiVal::Field(field),
-                )
             }
-            Ordering::Less => Err(ClassiError::new("classification levels must be provided")),
-            Ordering::Equal => {
-                let _ = self.root.add_node(
-                    &ClassiVal::Root,
-                    &ClassiVal::Classi(String::from(classis[0])),
+            if !pairs.is_empty() {
+                eprintln!(
+                    "warning: {} near-duplicate fields in the answer ({})",
+                    pairs.len(),
+                    near
                 );
-                self.root.add_node(
-                    &ClassiVal::Classi(String::from(classis[0])),
-                    &ClassiVal::Field(field),
-                )
             }
         }
-    }
-
-    fn all_leaves(&self) -> Vec<Vec<&ClassiNode>> {
-        let mut res = Vec::new();
-
-        let mut cur_q = Vec::<&ClassiNode>::new();
-        if let Some(ref subs) = self.root.subs {
-            for sub in subs {
-                ClassiTree::_collect_leave(sub, &mut cur_q, &mut res);
-                cur_q.clear();
+        if let Some(schema_file) = matches.get_one::<PathBuf>("schema") {
+            let schema = read_schema(schema_file, &normalize)?;
+            let phantoms = phantom_fields(&answer, &schema);
+            for (classis, field) in &phantoms {
+                eprintln!(
+                    "warning: field [{}] under [{}] is not in the schema",
+                    field,
+                    classis.join("/")
+                );
             }
-        }
-        res
-    }
-
-    fn _collect_leave<'a>(
-        node: &'a ClassiNode,
-        cur_q: &mut Vec<&'a ClassiNode>,
-        res: &mut Vec<Vec<&'a ClassiNode>>,
-    ) {
-        cur_q.push(node);
-        if let Some(ref subs) = node.subs {
-            for sub in subs {
-                ClassiTree::_collect_leave(sub, cur_q, res);
+            if !phantoms.is_empty() {
+                eprintln!(
+                    "warning: {} fields in the answer are not in the schema",
+                    phantoms.len()
+                );
             }
-            cur_q.pop();
-        } else {
-            res.push(cur_q.clone());
-            cur_q.pop();
         }
-    }
-
-    /// 和另一棵分类结果树做对比，生成对比结果
-    fn diff(&self, other: &ClassiTree) -> DiffResult {
-        let all_fields = self.all_leaves();
-        let mut res = Vec::new();
-        for field in all_fields {
-            let mut t_q = Vec::new();
-            let mut is_found = true;
-            for seg in &field {
-                match other.find_node(&seg.val) {
-                    Some(node) => match node.val {
-                        ClassiVal::Classi(ref classi) => t_q.push(classi.clone()),
-                        ClassiVal::Field(ref field) => {
-                            let unit = DiffUnit {
-                                classis: t_q.clone(),
-                                field: field.to_string(),
-                                field_exist: true,
-                            };
-                            res.push(unit);
-                        }
-                        _ => (),
-                    },
-                    None => is_found = false,
+        if matches.get_flag("check_hierarchy") {
+            for (name, tree) in [("solution", &solution), ("answer", &answer)] {
+                for conflict in tree.hierarchy_conflicts() {
+                    eprintln!("warning: {}: {}", name, conflict);
                 }
             }
-            if !is_found {
-                let unit = DiffUnit {
-                    classis: field[0..field.len()]
-                        .iter()
-                        .map(|n| match &n.val {
-                            ClassiVal::Classi(classi) => classi.clone(),
-                            _ => String::new(),
-                        })
-                        .collect(),
-                    field: field.last().unwrap().val.to_string(),
-                    field_exist: false,
-                };
-                res.push(unit);
+        }
+        let (solution, answer) = match matches.get_one::<String>("only_class") {
+            Some(class_path) => {
+                let path: Vec<&str> = class_path.split('/').collect();
+                let solution = solution.subtree(&path).ok_or_else(|| {
+                    anyhow::Error::msg(format!(
+                        "class path [{}] not found in the standard answer",
+                        class_path
+                    ))
+                })?;
+                let answer = answer.subtree(&path).unwrap_or_default();
+                (solution, answer)
+            }
+            None => (solution, answer),
+        };
+        let format = matches.get_one::<String>("format").unwrap();
+        if matches.get_flag("dump_tree") {
+            if format == "text" {
+                println!("{}", answer);
+            } else {
+                print!("{}", to_format(&answer, format)?);
             }
+            return Ok(());
         }
-
-        res
-    }
-}
-
-impl Display for ClassiTree {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.root.to_string(0).trim())
-    }
-}
-
-fn new_workbook_from_file(file_path: &PathBuf) -> anyhow::Result<Xlsx<BufReader<fs::File>>> {
-    let workbook: Xlsx<_> = open_workbook(file_path)?;
-    Ok(workbook)
-}
-
-fn new_workbook_from_bytes(bytes: &Vec<u8>) -> anyhow::Result<Xlsx<Cursor<&Vec<u8>>>> {
-    let cursor = Cursor::new(bytes);
-    let workbook: Xlsx<_> = open_workbook_from_rs(cursor)?;
-    Ok(workbook)
-}
-
-/// 读取分类结果，转化为分类树
-fn read_classi_result(file_path: &PathBuf, is_enc: bool) -> anyhow::Result<ClassiTree> {
-    let sheet = if is_enc {
-        let decrypt_result = decrypt_file(file_path).with_context(|| {
-            format!(
-                "failed to decrypt the standard answer file [{}]",
-                file_path.to_string_lossy()
-            )
-        })?;
-        let mut workbook = new_workbook_from_bytes(&decrypt_result)?;
-        workbook
-            .worksheet_range(CLASSI_SHEET)
-            .with_context(|| format!("failed to open the sheet [{}]", CLASSI_SHEET))?
-    } else {
-        let mut workbook = new_workbook_from_file(file_path)?;
-        workbook
-            .worksheet_range(CLASSI_SHEET)
-            .with_context(|| format!("failed to open the sheet [{}]", CLASSI_SHEET))?
-    };
-
-    let headers = sheet
-        .headers()
-        .ok_or(ClassiError::new("failed to retrieve the header"))?;
-
-    let mut classi_counter = 0;
-    for head in &headers {
-        if head == "数据库名称" {
-            break;
+        if matches.get_flag("taxonomy_patch") {
+            print!("{}", solution.taxonomy_diff(&answer).to_patch());
+            return Ok(());
+        }
+        if matches.get_flag("taxonomy_diff") {
+            solution.taxonomy_diff(&answer).print();
+            return Ok(());
+        }
+        if matches.get_flag("reconcile") {
+            let reconciliation = solution.reconcile(&answer);
+            reconciliation.print();
+            if let Some(csv_file) = matches.get_one::<PathBuf>("reconcile_csv") {
+                reconciliation.write_csv(csv_file)?;
+            }
+            return Ok(());
+        }
+        let unordered = matches.get_flag("unordered_levels");
+        let scope: MatchScope = matches.get_one::<String>("match_scope").unwrap().parse()?;
+        let report_fields = matches.get_one::<String>("report_fields");
+        if matches.get_flag("json_lines") {
+            let units: Box<dyn Iterator<Item = DiffUnit> + '_> = if unordered {
+                Box::new(solution.diff_unordered(&answer, scope).into_iter())
+            } else {
+                Box::new(solution.diff_iter_in_scope(&answer, scope))
+            };
+            let units: Box<dyn Iterator<Item = DiffUnit> + '_> = if matches.get_flag("intersection")
+            {
+                let common = solution.common_fields(&answer, scope);
+                Box::new(units.filter(move |unit| common.contains(&unit.field.scoped(scope))))
+            } else {
+                units
+            };
+            return match report_fields {
+                Some(list) => ReportColumns::parse(list, &answer)?
+                    .write_json_lines(units, io::stdout().lock()),
+                None => write_json_lines(units, io::stdout().lock()),
+            };
+        }
+        let confidence = *matches.get_one::<f64>("confidence").unwrap();
+        if !(confidence > 0.0 && confidence < 1.0) {
+            return Err(anyhow::Error::msg(format!(
+                "confidence level {} is not between 0 and 1",
+                confidence
+            )));
+        }
+        let sample = matches.get_one::<usize>("sample").copied();
+        let mut diff_res: DiffResult = if unordered {
+            solution.diff_unordered(&answer, scope)
         } else {
-            classi_counter += 1;
+            solution.diff_in_scope(&answer, scope)
+        };
+        if matches.get_flag("allow_partial_paths") {
+            mark_partial_paths(&answer, &mut diff_res);
         }
-    }
-
-    assert_ne!(
-        classi_counter, 0,
-        "the number of classification levels cannot be 0"
-    );
-    assert_eq!(headers.len(), classi_counter + 3, "header count error");
-
-    let maybe_row_len = sheet.get_size().0;
-    let range = sheet.range((1, 0), (maybe_row_len as u32, classi_counter as u32 + 2));
-
-    let mut tree = ClassiTree::new();
-    let mut field_filter = HashSet::<FieldMeta>::new();
-
-    for row in range.rows() {
-        if row.len() != classi_counter + 3 {
-            break;
+        if matches.get_flag("intersection") {
+            let common = solution.common_fields(&answer, scope);
+            let before = diff_res.len();
+            diff_res.retain(|unit| common.contains(&unit.field.scoped(scope)));
+            eprintln!(
+                "intersection: scoring {} of {} fields present in both the standard answer and the answer",
+                diff_res.len(),
+                before
+            );
+        }
+        if let Some(n) = sample {
+            let mut rng = match matches.get_one::<u64>("seed") {
+                Some(seed) => StdRng::seed_from_u64(*seed),
+                None => StdRng::from_entropy(),
+            };
+            diff_res = sample_diff(diff_res, n, &mut rng);
+        }
+        let mut report = ScoringReport::from(&diff_res);
+        report.set_average(matches.get_one::<String>("average").unwrap().parse()?);
+        if !matches.get_flag("quiet_empty_classes") {
+            report.include_empty_classes(solution.classes_without_fields());
+        }
+        if let Some(fields_file) = matches.get_one::<PathBuf>("fields_csv") {
+            let columns = ReportColumns::parse(
+                report_fields.map_or(ReportColumns::DEFAULT, String::as_str),
+                &answer,
+            )?;
+            columns.write_csv(diff_res.iter(), fs::File::create(fields_file)?)?;
+        }
+        if let Some(matched_file) = matches.get_one::<PathBuf>("export_matched") {
+            ReportColumns::parse("db,table,field,path", &answer)?.write_csv(
+                diff_res.iter().filter(|unit| unit.field_exist),
+                fs::File::create(matched_file)?,
+            )?;
+        }
+        if let Some(xlsx_file) = matches.get_one::<PathBuf>("report_xlsx") {
+            let columns = ReportColumns::parse(
+                report_fields.map_or(ReportColumns::DEFAULT, String::as_str),
+                &answer,
+            )?;
+            report.write_xlsx(&diff_res, &columns, xlsx_file)?;
+        }
+        if let Some(output_file) = matches.get_one::<PathBuf>("output") {
+            let output_format = match matches.get_one::<String>("output_format") {
+                Some(format) => format.as_str(),
+                None if output_file.extension().is_some_and(|ext| ext == "csv") => "csv",
+                None => "json",
+            };
+            report.write_output(&diff_res, output_file, output_format)?;
+        }
+        if let Some(summary_file) = matches.get_one::<PathBuf>("summary_csv") {
+            report.write_summary_csv(summary_file)?;
+        }
+        #[cfg(feature = "tui")]
+        if matches.get_flag("tui") {
+            if io::stdout().is_terminal() {
+                return sisyphus::classi::tui::run(&report, &diff_res);
+            }
+            eprintln!("warning: stdout is not a terminal, fall back to the text report");
+        }
+        if matches.get_flag("only_missing_classes") {
+            let depth = *matches.get_one::<usize>("group_depth").unwrap();
+            print_missing_classes(&diff_res, depth.max(1));
+            return Ok(());
+        }
+        if matches.get_flag("count_only") {
+            println!("{:.4}", report.overall);
+        } else if format != "text" {
+            print!("{}", to_format(&report, format)?);
         } else {
-            if row.is_empty() || row.first().unwrap().is_empty() {
-                continue;
+            if let Some(template_file) = matches.get_one::<PathBuf>("report_template") {
+                let template = fs::read_to_string(template_file)?;
+                print!("{}", render_report_template(&template, &report)?);
+            } else {
+                claussi_report(
+                    &diff_res,
+                    &report,
+                    matches.get_one::<usize>("report_width").copied(),
+                    matches.get_one::<usize>("explain_report").copied(),
+                    matches.get_one::<f64>("group_accuracy_threshold").copied(),
+                )?;
             }
-
-            let mut lvls = vec![];
-            for i in 0..classi_counter {
-                lvls.push(row.get(i).unwrap().get_string().unwrap());
+            if sample.is_some() {
+                print_sampled_accuracy(&report, confidence);
             }
-            let db = String::from(row.get(classi_counter).unwrap().get_string().unwrap());
-            let tb = String::from(row.get(classi_counter + 1).unwrap().get_string().unwrap());
-            let fd = String::from(row.get(classi_counter + 2).unwrap().get_string().unwrap());
-            let field_meta = FieldMeta(db, tb, fd);
-            if field_filter.contains(&field_meta) {
-                return Err(ClassiError::new("duplicated field detected").into());
-            } else {
-                field_filter.insert(field_meta.clone());
+            if matches.get_flag("depth_percentiles") {
+                print_depth_percentiles(&solution, &answer);
+            }
+            if matches.get_one::<String>("rollup").unwrap() == "table" {
+                rollup_misses_by_table(&diff_res);
+            } else if verbose {
+                explain_misses(&solution, &diff_res);
+            }
+            if matches.get_flag("detect_swaps") {
+                let swapped = detect_swaps(&answer, &diff_res);
+                if !swapped.is_empty() {
+                    println!(
+                        "likely table/field columns swapped: {} fields",
+                        swapped.len()
+                    );
+                    if verbose {
+                        for field in &swapped {
+                            println!("field [{}] found with table and field swapped", field);
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(min_accuracy) = matches.get_one::<f64>("min_accuracy") {
+            if report.overall < *min_accuracy {
+                if !matches.get_flag("count_only") {
+                    eprintln!(
+                        "overall accuracy {:.4} is below the minimum {:.4}",
+                        report.overall, min_accuracy
+                    );
+                }
+                process::exit(1);
+            }
+        }
+        if matches.get_flag("fail_on_class_below") {
+            let threshold = *matches.get_one::<f64>("group_accuracy_threshold").unwrap();
+            let below = report.classes_below(threshold);
+            if !below.is_empty() {
+                eprintln!(
+                    "{} classes are below the accuracy threshold {:.4}",
+                    below.len(),
+                    threshold
+                );
+                for class in &below {
+                    eprintln!("  {}: {:.4}", class.class, class.accuracy);
+                }
+                process::exit(1);
+            }
+        }
+        if matches.get_flag("fail_on_extra") {
+            let extra = solution.reconcile(&answer).extra;
+            if !extra.is_empty() {
+                eprintln!(
+                    "{} fields in the answer are not in the solution",
+                    extra.len()
+                );
+                for field in &extra {
+                    eprintln!("  {}", field);
+                }
+                process::exit(1);
             }
-
-            tree.add_node(&lvls, field_meta)?;
         }
-    }
-
-    Ok(tree)
-}
-
-/// 读取结果并将结果文件加密转存
-fn encrypt_file(ori_file: &PathBuf, enc_file: &PathBuf) -> anyhow::Result<()> {
-    let ori_file = fs::read(ori_file)?;
-    let key: &Key<Aes256Gcm> = ENC_KEY.into();
-    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-    let cipher = Aes256Gcm::new(key);
-    let cipher_content = cipher
-        .encrypt(&nonce, ori_file.as_ref())
-        .map_err(|e| anyhow::Error::msg(e.to_string()))?;
-    let mut enc_file = fs::File::create(enc_file)?;
-    let nonce_len = enc_file.write(&nonce)?;
-    if nonce_len != nonce.len() {
-        return Err(anyhow::Error::msg("failed to write the nonce"));
-    }
-    let _ = enc_file.write(&cipher_content)?;
-    Ok(())
-}
-
-/// 读取加密文件内容
-fn decrypt_file(enc_file: &PathBuf) -> anyhow::Result<Vec<u8>> {
-    let key: &Key<Aes256Gcm> = ENC_KEY.into();
-    let cipher = Aes256Gcm::new(key);
-
-    let mut enc_file = fs::File::open(enc_file)?;
-    let mut buf = Vec::new();
-    let _ = enc_file.read_to_end(&mut buf)?;
-    let nonce = &buf[..NONCE_LEN];
-    let cipher_content = &buf[NONCE_LEN..];
-
-    let plain_content = cipher
-        .decrypt(nonce.into(), cipher_content)
-        .map_err(|e| anyhow::Error::msg(e.to_string()))?;
-    Ok(plain_content)
-}
-
-fn main() -> anyhow::Result<()> {
-    let matches = Command::new("cls_profiler")
-        .about("数据分类探针")
-        .version("1.0.0")
-        .args([
-            arg!(answer: -a --answer <FILE> "指定分类结果文件的路径")
-                .value_parser(value_parser!(PathBuf)),
-            arg!(encrypt: -e --encrypt <FILE> "指定要加密的分类结果文件的路径")
-                .value_parser(value_parser!(PathBuf)),
-        ])
-        .arg_required_else_help(true)
-        .get_matches();
-
-    if let Some(ef) = matches.get_one::<PathBuf>("encrypt") {
-        encrypt_file(ef, &PathBuf::from(ENC_FILE_PATH))?;
-    }
-
-    if let Some(af) = matches.get_one::<PathBuf>("answer") {
-        let solution_file = PathBuf::from(ENC_FILE_PATH);
-        let solution = read_classi_result(&solution_file, true)?;
-        let answer = read_classi_result(af, false)?;
-        let diff_res: DiffResult = solution.diff(&answer);
-        claussi_report(&diff_res)?;
     }
 
     Ok(())
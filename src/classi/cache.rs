@@ -0,0 +1,319 @@
+//! 标准答案分类树的二进制缓存
+//!
+//! 缓存内容为`version || bincode(CachedTree)`，bincode固定使用大端序和定长整数编码，
+//! 不随机器字节序变化。缓存的结构变化时必须增加`CACHE_VERSION`，旧版本的缓存会被拒绝并重新生成
+//!
+//! 缓存中是解密后的标准答案，写入前用解密标准答案的密钥按第2版加密格式加密，
+//! `sisyphus-cache`加上缓存版本作为附加数据参与认证，没有密钥无法读取缓存
+//!
+//! 缓存文件保存在加密文件所在目录的`.sisyphus-cache`下，文件名为加密文件内容的SHA-256加上密钥的指纹，
+//! 加密文件的内容变化后自然对应新的缓存文件，同一密文解密得到的内容不变，所以不会读到过期的缓存；
+//! 换用其他密钥时不会命中用原密钥生成的缓存，而是和没有缓存时一样解密标准答案，密钥不对时照常报错
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    crypto::{self, EncKey},
+    tree::{ClassiTree, FieldMeta},
+    writer::write_atomic,
+};
+
+/// 缓存格式的版本，单元格转为文本的方式变化时也要增加，旧缓存中的分类名称可能和新读取的不同
+const CACHE_VERSION: u8 = 4;
+/// 加密缓存时附加数据的前缀，之后为缓存版本
+const CACHE_AAD: &[u8] = b"sisyphus-cache";
+/// 缓存目录的名称
+const CACHE_DIR: &str = ".sisyphus-cache";
+
+/// 缓存中保存的分类树，只记录每个字段的分类路径，读取时重新构建分类树
+#[derive(Serialize, Deserialize)]
+struct CachedTree {
+    /// 生成缓存时影响解析结果的选项，和本次的选项不一致时缓存无效
+    options: String,
+    level_names: Vec<String>,
+    fields: Vec<(Vec<String>, FieldMeta)>,
+}
+
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_big_endian()
+        .with_fixint_encoding()
+}
+
+/// 将分类树编码为带版本号的二进制内容，`options`为生成分类树时影响解析结果的选项
+pub fn encode_tree(tree: &ClassiTree, options: &str) -> anyhow::Result<Vec<u8>> {
+    let fields = tree
+        .field_paths()
+        .map(|(classis, field)| {
+            (
+                classis.into_iter().map(String::from).collect(),
+                field.clone(),
+            )
+        })
+        .collect();
+    let cached = CachedTree {
+        options: options.to_string(),
+        level_names: tree.level_names.clone(),
+        fields,
+    };
+
+    let mut res = vec![CACHE_VERSION];
+    res.extend(bincode_options().serialize(&cached)?);
+    Ok(res)
+}
+
+/// 解码带版本号的二进制内容，版本号或者选项不一致时返回错误
+pub fn decode_tree(bytes: &[u8], options: &str) -> anyhow::Result<ClassiTree> {
+    let (version, content) = bytes
+        .split_first()
+        .ok_or_else(|| anyhow::Error::msg("the cache file is empty"))?;
+    if *version != CACHE_VERSION {
+        return Err(anyhow::Error::msg(format!(
+            "cache version {} does not match the expected version {}",
+            version, CACHE_VERSION
+        )));
+    }
+    let cached: CachedTree = bincode_options().deserialize(content)?;
+    if cached.options != options {
+        return Err(anyhow::Error::msg(format!(
+            "cache was built with options [{}] instead of [{}]",
+            cached.options, options
+        )));
+    }
+
+    let mut tree = ClassiTree::new();
+    tree.level_names = cached.level_names;
+    for (classis, field) in cached.fields {
+        let classis: Vec<&str> = classis.iter().map(String::as_str).collect();
+        tree.add_node(&classis, field)?;
+    }
+    Ok(tree)
+}
+
+/// 加密缓存时使用的附加数据
+fn cache_aad(version: u8) -> Vec<u8> {
+    [CACHE_AAD, &[version]].concat()
+}
+
+/// 编码并加密分类树，得到写入缓存文件的内容
+pub fn seal_tree(tree: &ClassiTree, options: &str, key: &EncKey) -> anyhow::Result<Vec<u8>> {
+    let plain = encode_tree(tree, options)?;
+    Ok(crypto::encrypt_bytes_with_aad(
+        &plain,
+        &cache_aad(CACHE_VERSION),
+        key,
+    )?)
+}
+
+/// 解密并解码缓存文件的内容，密钥、缓存版本或者选项不一致时返回错误
+pub fn open_tree(bytes: &[u8], options: &str, key: &EncKey) -> anyhow::Result<ClassiTree> {
+    let plain = crypto::decrypt_bytes_with_aad(bytes, &cache_aad(CACHE_VERSION), key)?;
+    decode_tree(&plain, options)
+}
+
+/// 加密文件内容的SHA-256，十六进制小写
+pub fn file_hash(enc_file: &Path) -> anyhow::Result<String> {
+    let digest = Sha256::digest(fs::read(enc_file)?);
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// 加密文件对应的缓存文件路径，如`./fix_e`对应`./.sisyphus-cache/<hash>-<fingerprint>`
+pub fn cache_path(enc_file: &Path, hash: &str, key: &EncKey) -> PathBuf {
+    enc_file
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(CACHE_DIR)
+        .join(format!("{}-{}", hash, key.fingerprint()))
+}
+
+/// 优先从缓存读取标准答案，缓存不存在、版本或者选项不一致时调用`build`重新生成并写入缓存，
+/// 缓存命中时不需要解密和解析Excel
+///
+/// `options`为影响解析结果的选项，`rebuild`为真时忽略已有的缓存，`key`为解密标准答案的密钥，
+/// 同时用于加密缓存
+pub fn load_or_build<F>(
+    enc_file: &Path,
+    options: &str,
+    rebuild: bool,
+    key: &EncKey,
+    build: F,
+) -> anyhow::Result<ClassiTree>
+where
+    F: FnOnce() -> anyhow::Result<ClassiTree>,
+{
+    let cache_file = match file_hash(enc_file) {
+        Ok(hash) => cache_path(enc_file, &hash, key),
+        // 加密文件无法读取时交给`build`报告错误
+        Err(_) => return build(),
+    };
+    if !rebuild && cache_file.exists() {
+        match fs::read(&cache_file)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| open_tree(&bytes, options, key))
+        {
+            Ok(tree) => return Ok(tree),
+            Err(e) => eprintln!(
+                "warning: ignore the cache [{}]: {}",
+                cache_file.to_string_lossy(),
+                e
+            ),
+        }
+    }
+
+    let tree = build()?;
+    let written = seal_tree(&tree, options, key).and_then(|bytes| {
+        if let Some(dir) = cache_file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(write_atomic(&cache_file, &bytes)?)
+    });
+    if let Err(e) = written {
+        eprintln!(
+            "warning: failed to write the cache [{}]: {}",
+            cache_file.to_string_lossy(),
+            e
+        );
+    }
+    Ok(tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    /// 测试用的临时目录，每个测试使用不同的名称，互不影响
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sisyphus-cache-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn sample() -> ClassiTree {
+        let mut tree = ClassiTree::new();
+        tree.level_names = vec![String::from("一级分类"), String::from("二级分类")];
+        for (classis, field) in [
+            (["个人信息", "身份信息"], "id_card"),
+            (["个人信息", "联系方式"], "phone"),
+            (["交易信息", "订单"], "order_no"),
+        ] {
+            tree.add_node(
+                &classis,
+                FieldMeta("crm".into(), "user".into(), field.into()),
+            )
+            .unwrap();
+        }
+        tree
+    }
+
+    fn paths(tree: &ClassiTree) -> Vec<(String, String)> {
+        tree.field_paths()
+            .map(|(classis, field)| (classis.join("/"), field.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn cache_round_trip_is_encrypted() {
+        let dir = temp_dir("round-trip");
+        let enc_file = dir.join("solution_e");
+        fs::write(&enc_file, b"encrypted solution").unwrap();
+        let key = EncKey::new([3; 32]);
+
+        let built = load_or_build(&enc_file, "trim", false, &key, || Ok(sample())).unwrap();
+        let cache_file = cache_path(&enc_file, &file_hash(&enc_file).unwrap(), &key);
+        let bytes = fs::read(&cache_file).unwrap();
+        assert!(!bytes.windows(7).any(|w| w == b"id_card"));
+
+        let cached = load_or_build(&enc_file, "trim", false, &key, || {
+            panic!("the cache should be used")
+        })
+        .unwrap();
+        assert_eq!(paths(&cached), paths(&built));
+        assert_eq!(cached.level_name(1), "二级分类");
+
+        let rebuilt = Cell::new(false);
+        load_or_build(&enc_file, "lower", false, &key, || {
+            rebuilt.set(true);
+            Ok(sample())
+        })
+        .unwrap();
+        assert!(rebuilt.get());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cache_of_another_version_is_rebuilt() {
+        let key = EncKey::new([5; 32]);
+        let tree = sample();
+        let old = crypto::encrypt_bytes_with_aad(
+            &encode_tree(&tree, "").unwrap(),
+            &cache_aad(CACHE_VERSION - 1),
+            &key,
+        )
+        .unwrap();
+        assert!(open_tree(&old, "", &key).is_err());
+
+        let mut plain = encode_tree(&tree, "").unwrap();
+        plain[0] = CACHE_VERSION + 1;
+        assert!(decode_tree(&plain, "").is_err());
+
+        let dir = temp_dir("version");
+        let enc_file = dir.join("solution_e");
+        fs::write(&enc_file, b"encrypted solution").unwrap();
+        let cache_file = cache_path(&enc_file, &file_hash(&enc_file).unwrap(), &key);
+        fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+        fs::write(&cache_file, &old).unwrap();
+
+        let rebuilt = Cell::new(false);
+        let loaded = load_or_build(&enc_file, "", false, &key, || {
+            rebuilt.set(true);
+            Ok(sample())
+        })
+        .unwrap();
+        assert!(rebuilt.get());
+        assert_eq!(paths(&loaded), paths(&tree));
+        assert!(open_tree(&fs::read(&cache_file).unwrap(), "", &key).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cache_is_not_shared_across_keys() {
+        let dir = temp_dir("keys");
+        let enc_file = dir.join("solution_e");
+        fs::write(&enc_file, b"encrypted solution").unwrap();
+        let key = EncKey::new([3; 32]);
+        let other = EncKey::new([4; 32]);
+        load_or_build(&enc_file, "", false, &key, || Ok(sample())).unwrap();
+
+        // 换用其他密钥时必须重新解密标准答案，这里模拟密钥不对导致的解密失败
+        let rebuilt = Cell::new(false);
+        let res = load_or_build(&enc_file, "", false, &other, || {
+            rebuilt.set(true);
+            Err(anyhow::Error::msg("wrong key"))
+        });
+        assert!(rebuilt.get());
+        assert!(res.is_err());
+
+        let hash = file_hash(&enc_file).unwrap();
+        assert_ne!(
+            cache_path(&enc_file, &hash, &key),
+            cache_path(&enc_file, &hash, &other)
+        );
+        let cached = fs::read(cache_path(&enc_file, &hash, &key)).unwrap();
+        assert!(open_tree(&cached, "", &other).is_err());
+        assert!(open_tree(&cached, "", &key).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
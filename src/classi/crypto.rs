@@ -0,0 +1,699 @@
+//! 标准答案文件的加解密
+//!
+//! 使用AES-256-GCM加密，加密文件的格式为`CLSE || 2 || records`，每条记录为`tag || varint(len) || bytes`，
+//! 记录按tag从小到大排列，解密时跳过不认识的tag。明文可以在加密前压缩，压缩方式记录在codec记录中，
+//! 同时作为附加数据（AAD）参与认证，解密并验证后再解压。
+//! 早期版本的加密文件格式为`CLSE || 1 || nonce || ciphertext`，更早的没有文件头，格式为`nonce || ciphertext`
+//!
+//! 密钥由调用方通过`EncKey`传入，依次来自`--key-file`指定的文件、`SISYPHUS_KEY`环境变量，
+//! 都没有时使用程序内置的密钥
+
+use std::{
+    env,
+    fmt::{Debug, Display},
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use aes_gcm::{
+    aead::{Aead, OsRng, Payload},
+    AeadCore, Aes256Gcm, Key, KeyInit,
+};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+
+use super::writer::write_atomic;
+use crate::error::{Result, SisyphusError};
+
+/// 密钥的长度，AES-256使用32字节的密钥
+pub const KEY_LEN: usize = 32;
+/// 指定密钥的环境变量，内容为十六进制编码的32字节
+pub const KEY_ENV: &str = "SISYPHUS_KEY";
+
+/// 内置的密钥，只在没有通过文件或者环境变量指定密钥时使用
+const ENC_KEY: &[u8; KEY_LEN] = &[
+    232, 222, 212, 202, 166, 177, 188, 199, 87, 34, 44, 10, 102, 1, 9, 0, 32, 22, 22, 20, 136, 177,
+    128, 199, 87, 32, 44, 10, 102, 2, 4, 6,
+];
+const NONCE_LEN: usize = 96 / 8;
+const MAGIC: &[u8; 4] = b"CLSE";
+const FORMAT_V1: u8 = 1;
+const FORMAT_VERSION: u8 = 2;
+
+/// 第2版格式中各条记录的tag，新的记录只能使用新的tag，已有tag的含义不能改变
+const TAG_SALT: u8 = 1;
+const TAG_NONCE: u8 = 2;
+const TAG_AAD: u8 = 3;
+const TAG_CIPHERTEXT: u8 = 4;
+const TAG_CODEC: u8 = 5;
+
+/// 密钥的来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    /// `--key-file`指定的文件
+    File(PathBuf),
+    /// `SISYPHUS_KEY`环境变量
+    Env,
+    /// 调用方直接传入的字节
+    Bytes,
+    /// 程序内置的密钥
+    BuiltIn,
+}
+
+impl Display for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeySource::File(path) => write!(f, "file [{}]", path.to_string_lossy()),
+            KeySource::Env => write!(f, "environment variable {}", KEY_ENV),
+            KeySource::Bytes => write!(f, "bytes"),
+            KeySource::BuiltIn => write!(f, "built-in"),
+        }
+    }
+}
+
+/// 加解密使用的密钥及其来源，`Debug`只输出指纹，不输出密钥本身
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncKey {
+    bytes: [u8; KEY_LEN],
+    source: KeySource,
+}
+
+impl EncKey {
+    pub fn new(bytes: [u8; KEY_LEN]) -> Self {
+        EncKey {
+            bytes,
+            source: KeySource::Bytes,
+        }
+    }
+
+    /// 程序内置的密钥
+    pub fn built_in() -> Self {
+        EncKey {
+            bytes: *ENC_KEY,
+            source: KeySource::BuiltIn,
+        }
+    }
+
+    /// 读取文件的原始内容作为密钥，长度必须是32字节
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read(path)?;
+        let bytes = <[u8; KEY_LEN]>::try_from(content.as_slice()).map_err(|_| {
+            SisyphusError::InvalidKey(format!(
+                "the key file [{}] is {} bytes instead of {}",
+                path.to_string_lossy(),
+                content.len(),
+                KEY_LEN
+            ))
+        })?;
+        Ok(EncKey {
+            bytes,
+            source: KeySource::File(path.to_path_buf()),
+        })
+    }
+
+    /// 解析十六进制编码的密钥，忽略首尾的空白
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let hex = hex.trim();
+        if hex.len() != KEY_LEN * 2 {
+            return Err(SisyphusError::InvalidKey(format!(
+                "expected {} hex digits, found {}",
+                KEY_LEN * 2,
+                hex.len()
+            )));
+        }
+        let mut bytes = [0u8; KEY_LEN];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = hex
+                .get(i * 2..i * 2 + 2)
+                .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| {
+                    SisyphusError::InvalidKey(format!("invalid hex digits at position {}", i * 2))
+                })?;
+        }
+        Ok(EncKey::new(bytes))
+    }
+
+    /// 依次使用`key_file`、`SISYPHUS_KEY`环境变量、内置的密钥，
+    /// 指定了文件或者环境变量但内容不对时报错，不会退回内置的密钥
+    pub fn resolve(key_file: Option<&Path>) -> Result<Self> {
+        if let Some(path) = key_file {
+            return EncKey::from_file(path);
+        }
+        match env::var(KEY_ENV) {
+            Ok(hex) => {
+                let mut key = EncKey::from_hex(&hex)
+                    .map_err(|e| SisyphusError::InvalidKey(format!("{}: {}", KEY_ENV, e)))?;
+                key.source = KeySource::Env;
+                Ok(key)
+            }
+            Err(env::VarError::NotPresent) => Ok(EncKey::built_in()),
+            Err(env::VarError::NotUnicode(_)) => Err(SisyphusError::InvalidKey(format!(
+                "{} is not valid unicode",
+                KEY_ENV
+            ))),
+        }
+    }
+
+    /// 密钥的指纹，即密钥SHA-256摘要的前8个字节的十六进制，用于确认两个程序使用同一个密钥，
+    /// 不会暴露密钥本身
+    pub fn fingerprint(&self) -> String {
+        Sha256::digest(self.bytes)[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    pub fn source(&self) -> &KeySource {
+        &self.source
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        let key: &Key<Aes256Gcm> = (&self.bytes).into();
+        Aes256Gcm::new(key)
+    }
+}
+
+impl Default for EncKey {
+    fn default() -> Self {
+        EncKey::built_in()
+    }
+}
+
+impl Debug for EncKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncKey")
+            .field("fingerprint", &self.fingerprint())
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+/// 加密文件格式错误
+fn malformed(msg: impl Into<String>) -> SisyphusError {
+    SisyphusError::MalformedEncryptedFile(msg.into())
+}
+
+/// 加密文件的格式版本
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FormatVersion {
+    /// 根据文件头自动识别
+    #[default]
+    Auto,
+    /// 没有文件头的`nonce || ciphertext`
+    Legacy,
+    /// `CLSE || 1 || nonce || ciphertext`
+    V1,
+    /// `CLSE || 2 || records`
+    V2,
+}
+
+impl FromStr for FormatVersion {
+    type Err = SisyphusError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(FormatVersion::Auto),
+            "0" => Ok(FormatVersion::Legacy),
+            "1" => Ok(FormatVersion::V1),
+            "2" => Ok(FormatVersion::V2),
+            _ => Err(SisyphusError::InvalidOption(format!(
+                "unknown encrypted file format version [{}]",
+                s
+            ))),
+        }
+    }
+}
+
+/// 加密前明文的压缩方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// 不压缩，和不支持压缩的旧版本程序兼容
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// 写入codec记录的编号
+    fn id(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Gzip => 1,
+            Compression::Zstd => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self> {
+        match id {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Gzip),
+            2 => Ok(Compression::Zstd),
+            _ => Err(malformed(format!("unknown compression codec {}", id))),
+        }
+    }
+
+    fn compress(self, plain: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(plain.to_vec()),
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(plain)?;
+                Ok(encoder.finish()?)
+            }
+            Compression::Zstd => Ok(zstd::encode_all(plain, 0)?),
+        }
+    }
+
+    /// 解压认证通过的明文，内容不是该格式的压缩数据时报错
+    fn decompress(self, compressed: Vec<u8>) -> Result<Vec<u8>> {
+        let res = match self {
+            Compression::None => return Ok(compressed),
+            Compression::Gzip => {
+                let mut plain = Vec::new();
+                flate2::read::GzDecoder::new(compressed.as_slice())
+                    .read_to_end(&mut plain)
+                    .map(|_| plain)
+            }
+            Compression::Zstd => zstd::decode_all(compressed.as_slice()),
+        };
+        res.map_err(|e| {
+            malformed(format!(
+                "the header says the content is compressed with {} but it is not valid: {}",
+                self, e
+            ))
+        })
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Compression {
+    type Err = SisyphusError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(SisyphusError::InvalidOption(format!(
+                "unknown compression [{}], expected gzip, zstd or none",
+                s
+            ))),
+        }
+    }
+}
+
+/// 加密内存中的内容，返回第2版格式的内容
+pub fn encrypt_bytes(plain: &[u8], key: &EncKey) -> Result<Vec<u8>> {
+    encrypt_bytes_with(plain, Compression::None, key)
+}
+
+/// 按指定的方式压缩后加密内存中的内容，返回第2版格式的内容
+///
+/// 压缩时codec记录同时作为附加数据参与认证，篡改压缩方式会导致解密失败；不压缩时不写入codec记录，
+/// 和旧版本程序生成的文件相同
+pub fn encrypt_bytes_with(plain: &[u8], compression: Compression, key: &EncKey) -> Result<Vec<u8>> {
+    let plain = compression.compress(plain)?;
+    let codec = [compression.id()];
+    let aad: &[u8] = if compression == Compression::None {
+        &[]
+    } else {
+        &codec
+    };
+    seal(&plain, TAG_CODEC, aad, key)
+}
+
+/// 加密内存中的内容，`aad`作为附加数据写入aad记录并参与认证，返回第2版格式的内容；
+/// 用`decrypt_bytes_with_aad`解密，附加数据不同时解密失败
+pub fn encrypt_bytes_with_aad(plain: &[u8], aad: &[u8], key: &EncKey) -> Result<Vec<u8>> {
+    seal(plain, TAG_AAD, aad, key)
+}
+
+/// 加密并写出第2版格式的内容，附加数据不为空时以`aad_tag`记录写入
+fn seal(plain: &[u8], aad_tag: u8, aad: &[u8], key: &EncKey) -> Result<Vec<u8>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let cipher = key.cipher();
+    let payload = Payload { msg: plain, aad };
+    let cipher_content = cipher
+        .encrypt(&nonce, payload)
+        .map_err(|e| SisyphusError::Other(format!("failed to encrypt: {}", e)))?;
+
+    let mut enc_content =
+        Vec::with_capacity(MAGIC.len() + 1 + nonce.len() + cipher_content.len() + 16);
+    enc_content.extend_from_slice(MAGIC);
+    enc_content.push(FORMAT_VERSION);
+    write_record(&mut enc_content, TAG_NONCE, &nonce);
+    write_record(&mut enc_content, TAG_CIPHERTEXT, &cipher_content);
+    if !aad.is_empty() {
+        write_record(&mut enc_content, aad_tag, aad);
+    }
+    Ok(enc_content)
+}
+
+/// 写入一条`tag || varint(len) || bytes`记录，长度使用LEB128编码
+fn write_record(buf: &mut Vec<u8>, tag: u8, bytes: &[u8]) {
+    buf.push(tag);
+    let mut len = bytes.len() as u64;
+    loop {
+        let b = (len & 0x7f) as u8;
+        len >>= 7;
+        if len == 0 {
+            buf.push(b);
+            break;
+        }
+        buf.push(b | 0x80);
+    }
+    buf.extend_from_slice(bytes);
+}
+
+/// 读取LEB128编码的长度，返回长度和剩余的内容
+fn read_varint(bytes: &[u8]) -> Result<(usize, &[u8])> {
+    let mut len = 0u64;
+    for (i, b) in bytes.iter().enumerate().take(10) {
+        len |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            let len =
+                usize::try_from(len).map_err(|_| malformed("the record length is too large"))?;
+            return Ok((len, &bytes[i + 1..]));
+        }
+    }
+    Err(malformed("invalid record length"))
+}
+
+/// 第2版格式中解析出的记录
+#[derive(Default)]
+struct Records<'a> {
+    salt: Option<&'a [u8]>,
+    nonce: Option<&'a [u8]>,
+    aad: Option<&'a [u8]>,
+    ciphertext: Option<&'a [u8]>,
+    codec: Option<&'a [u8]>,
+}
+
+/// 依次解析各条记录，不认识的tag直接跳过
+fn parse_records(mut rest: &[u8]) -> Result<Records<'_>> {
+    let mut records = Records::default();
+    while let Some((&tag, body)) = rest.split_first() {
+        let (len, body) = read_varint(body)?;
+        if body.len() < len {
+            return Err(malformed(format!(
+                "record with tag {} is truncated, expected {} bytes but {} left",
+                tag,
+                len,
+                body.len()
+            )));
+        }
+        let (value, remain) = body.split_at(len);
+        match tag {
+            TAG_SALT => records.salt = Some(value),
+            TAG_NONCE => records.nonce = Some(value),
+            TAG_AAD => records.aad = Some(value),
+            TAG_CIPHERTEXT => records.ciphertext = Some(value),
+            TAG_CODEC => records.codec = Some(value),
+            _ => (),
+        }
+        rest = remain;
+    }
+    Ok(records)
+}
+
+/// 解密内存中的内容，根据文件头自动识别格式
+pub fn decrypt_bytes(enc_content: &[u8], key: &EncKey) -> Result<Vec<u8>> {
+    decrypt_bytes_as(enc_content, FormatVersion::Auto, key)
+}
+
+/// 按指定的格式版本解密内存中的内容
+///
+/// 自动识别时，以`CLSE`开头的内容先按新格式解密，失败后再按没有文件头的旧格式解密，
+/// 旧格式的nonce恰好以`CLSE`开头时也能正确解密
+pub fn decrypt_bytes_as(
+    enc_content: &[u8],
+    version: FormatVersion,
+    key: &EncKey,
+) -> Result<Vec<u8>> {
+    match version {
+        FormatVersion::Legacy => decrypt_legacy(enc_content, key),
+        FormatVersion::V1 | FormatVersion::V2 => decrypt_versioned(enc_content, version, key),
+        FormatVersion::Auto => {
+            if enc_content.starts_with(MAGIC) {
+                decrypt_versioned(enc_content, FormatVersion::Auto, key)
+                    .or_else(|e| decrypt_legacy(enc_content, key).map_err(|_| e))
+            } else {
+                decrypt_legacy(enc_content, key)
+            }
+        }
+    }
+}
+
+/// 解密`encrypt_bytes_with_aad`加密的内容，aad记录和`aad`不同时返回错误
+pub fn decrypt_bytes_with_aad(enc_content: &[u8], aad: &[u8], key: &EncKey) -> Result<Vec<u8>> {
+    let rest = enc_content
+        .strip_prefix(MAGIC)
+        .and_then(|body| body.strip_prefix(&[FORMAT_VERSION]))
+        .ok_or_else(|| malformed("the content is not in the version 2 format"))?;
+    let found = parse_records(rest)?.aad.unwrap_or_default();
+    if found != aad {
+        return Err(malformed(format!(
+            "the associated data {:?} does not match the expected {:?}",
+            String::from_utf8_lossy(found),
+            String::from_utf8_lossy(aad)
+        )));
+    }
+    decrypt_v2(rest, key)
+}
+
+/// 解密带`CLSE`文件头的内容，`version`为`Auto`时使用文件头中的版本
+fn decrypt_versioned(enc_content: &[u8], version: FormatVersion, key: &EncKey) -> Result<Vec<u8>> {
+    let body = enc_content
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| malformed("the encrypted file does not start with CLSE"))?;
+    match (body.split_first(), version) {
+        (Some((&FORMAT_V1, rest)), FormatVersion::V1 | FormatVersion::Auto) => {
+            decrypt_legacy(rest, key)
+        }
+        (Some((&FORMAT_VERSION, rest)), FormatVersion::V2 | FormatVersion::Auto) => {
+            decrypt_v2(rest, key)
+        }
+        (Some((found, _)), _) => Err(malformed(format!(
+            "unsupported encrypted file version {}",
+            found
+        ))),
+        (None, _) => Err(malformed("the encrypted file is too short")),
+    }
+}
+
+/// 解密第2版格式的记录，nonce和密文都是必需的记录，有codec记录时解密后按其解压
+fn decrypt_v2(rest: &[u8], key: &EncKey) -> Result<Vec<u8>> {
+    let records = parse_records(rest)?;
+    if records.salt.is_some() {
+        return Err(malformed(
+            "the encrypted file uses a passphrase, which is not supported yet",
+        ));
+    }
+    let nonce = records
+        .nonce
+        .ok_or_else(|| malformed("the encrypted file has no nonce"))?;
+    if nonce.len() != NONCE_LEN {
+        return Err(malformed(format!(
+            "the nonce is {} bytes instead of {}",
+            nonce.len(),
+            NONCE_LEN
+        )));
+    }
+    let ciphertext = records
+        .ciphertext
+        .ok_or_else(|| malformed("the encrypted file has no ciphertext"))?;
+
+    let compression = match records.codec {
+        None => Compression::None,
+        Some([id]) => Compression::from_id(*id)?,
+        Some(_) => return Err(malformed("the codec record must be one byte")),
+    };
+
+    let cipher = key.cipher();
+    // codec记录即为压缩时的附加数据
+    let payload = Payload {
+        msg: ciphertext,
+        aad: records.aad.or(records.codec).unwrap_or_default(),
+    };
+    let plain = cipher
+        .decrypt(nonce.into(), payload)
+        .map_err(|_| SisyphusError::WrongKey)?;
+    compression.decompress(plain)
+}
+
+/// 解密`nonce || ciphertext`形式的内容
+fn decrypt_legacy(enc_content: &[u8], key: &EncKey) -> Result<Vec<u8>> {
+    if enc_content.len() <= NONCE_LEN {
+        return Err(malformed("the encrypted file is too short"));
+    }
+    let cipher = key.cipher();
+
+    let nonce = &enc_content[..NONCE_LEN];
+    let cipher_content = &enc_content[NONCE_LEN..];
+    let plain_content = cipher
+        .decrypt(nonce.into(), cipher_content)
+        .map_err(|_| SisyphusError::WrongKey)?;
+    Ok(plain_content)
+}
+
+/// 读取结果并将结果文件按`compression`压缩后加密转存
+///
+/// 通过`write_atomic`写入，中途失败时`enc_file`保持原样，不会留下不完整的加密文件
+pub fn encrypt_file(
+    ori_file: &Path,
+    enc_file: &Path,
+    compression: Compression,
+    key: &EncKey,
+) -> Result<()> {
+    let ori_file = fs::read(ori_file)?;
+    let enc_content = encrypt_bytes_with(&ori_file, compression, key)?;
+    write_atomic(enc_file, &enc_content)?;
+    Ok(())
+}
+
+/// 使用`jobs`个线程并行加密多个文件，加密文件保存到`out_dir`下，文件名为原文件名加上`.enc`
+///
+/// `jobs`为0时使用CPU核数。每个文件独立加密，某个文件失败不影响其它文件，
+/// 每个文件完成后调用`on_done`，返回结果的顺序和`files`一致
+pub fn encrypt_batch<F>(
+    files: &[PathBuf],
+    out_dir: &Path,
+    jobs: usize,
+    compression: Compression,
+    key: &EncKey,
+    on_done: F,
+) -> Result<Vec<Result<PathBuf>>>
+where
+    F: Fn(&Path, &Result<PathBuf>) + Sync,
+{
+    fs::create_dir_all(out_dir)?;
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(|e| SisyphusError::Other(e.to_string()))?;
+    let res = pool.install(|| {
+        files
+            .par_iter()
+            .map(|file| {
+                let res = file
+                    .file_name()
+                    .ok_or_else(|| SisyphusError::Other(String::from("not a file")))
+                    .map(|name| {
+                        let mut enc_name = name.to_owned();
+                        enc_name.push(".enc");
+                        out_dir.join(enc_name)
+                    })
+                    .and_then(|enc_file| {
+                        encrypt_file(file, &enc_file, compression, key).map(|_| enc_file)
+                    });
+                on_done(file, &res);
+                res
+            })
+            .collect()
+    });
+    Ok(res)
+}
+
+/// 读取加密文件内容，根据文件头自动识别格式
+pub fn decrypt_file(enc_file: &PathBuf, key: &EncKey) -> Result<Vec<u8>> {
+    decrypt_file_as(enc_file, FormatVersion::Auto, key)
+}
+
+/// 按指定的格式版本读取加密文件内容
+///
+/// 加密文件通过内存映射读取，解密前不需要把整个文件复制到堆上，堆内存的峰值约为明文的大小，
+/// 而不是密文加明文的大小，见`cargo bench --bench decrypt_memory`
+pub fn decrypt_file_as(
+    enc_file: &PathBuf,
+    version: FormatVersion,
+    key: &EncKey,
+) -> Result<Vec<u8>> {
+    let enc_file = fs::File::open(enc_file)?;
+    if enc_file.metadata()?.len() == 0 {
+        return Err(malformed("the encrypted file is empty"));
+    }
+    // SAFETY: 映射只用于读取，解密期间不会修改文件
+    let mapped = unsafe { Mmap::map(&enc_file)? };
+    decrypt_bytes_as(&mapped, version, key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &[u8] = b"classification result";
+
+    /// 没有文件头的旧格式`nonce || ciphertext`
+    fn encrypt_legacy(plain: &[u8], key: &EncKey) -> Vec<u8> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut enc = nonce.to_vec();
+        enc.extend(key.cipher().encrypt(&nonce, plain).unwrap());
+        enc
+    }
+
+    #[test]
+    fn decrypt_file_detects_headered_and_legacy_formats() {
+        let dir = std::env::temp_dir().join(format!("sisyphus-crypto-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let key = EncKey::new([9; KEY_LEN]);
+
+        let legacy = encrypt_legacy(PLAIN, &key);
+        let v1 = [MAGIC.as_slice(), &[FORMAT_V1], &legacy].concat();
+        let files = [
+            ("v2", encrypt_bytes(PLAIN, &key).unwrap()),
+            (
+                "v2_gzip",
+                encrypt_bytes_with(PLAIN, Compression::Gzip, &key).unwrap(),
+            ),
+            ("v1", v1),
+            ("legacy", legacy),
+        ];
+        for (name, enc) in files {
+            let enc_file = dir.join(name);
+            fs::write(&enc_file, enc).unwrap();
+            assert_eq!(decrypt_file(&enc_file, &key).unwrap(), PLAIN, "{}", name);
+        }
+
+        let legacy_file = dir.join("legacy");
+        assert!(matches!(
+            decrypt_file_as(&legacy_file, FormatVersion::V2, &key),
+            Err(SisyphusError::MalformedEncryptedFile(_))
+        ));
+        assert!(matches!(
+            decrypt_file(&legacy_file, &EncKey::new([1; KEY_LEN])),
+            Err(SisyphusError::WrongKey)
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_option_values_are_invalid_options() {
+        assert_eq!("zstd".parse::<Compression>().unwrap(), Compression::Zstd);
+        assert!(matches!(
+            "lz4".parse::<Compression>(),
+            Err(SisyphusError::InvalidOption(msg)) if msg.contains("[lz4]")
+        ));
+        assert_eq!("1".parse::<FormatVersion>().unwrap(), FormatVersion::V1);
+        assert!(matches!(
+            "3".parse::<FormatVersion>(),
+            Err(SisyphusError::InvalidOption(_))
+        ));
+    }
+}
@@ -0,0 +1,46 @@
+//! 数据分类探针的核心逻辑
+//!
+//! - `tree`：分类树的构建和对比
+//! - `cache`：标准答案分类树的二进制缓存
+//! - `reader`：从Excel文档读取分类结果
+//! - `crypto`：标准答案文件的加解密
+//! - `report`：分类成绩的统计和输出
+//! - `stats`：置信区间、百分位数等统计方法
+//! - `text`：终端输出按显示宽度截断和补齐
+//! - `trend`：多个不同时间的分类结果的成绩趋势
+//! - `writer`：将分类树输出为分类结果格式的表格
+//! - `tui`：浏览对比结果的终端界面，需要开启`tui`特性
+//! - `serve`：为上传的分类结果打分的HTTP服务，需要开启`serve`特性
+
+pub mod cache;
+pub mod crypto;
+pub mod reader;
+pub mod report;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod stats;
+pub mod text;
+pub mod tree;
+pub mod trend;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod writer;
+
+pub use crypto::{decrypt_file, encrypt_file, EncKey};
+pub use reader::{
+    build_tree_from_range, near_duplicates, phantom_fields, read_classi_result,
+    read_classi_result_from_bytes, read_classi_results, read_classi_sheet, read_schema,
+    read_template_levels, validate_encrypted, BlankRow, DroppedDuplicate, DuplicateLog,
+    FieldNormalize, InputFormat, ParseConfig, ParseConfigBuilder,
+};
+pub use report::{
+    answer_churn, claussi_report, detect_swaps, explain_misses, load_diff_result,
+    mark_partial_paths, missing_classes, print_depth_percentiles, print_grouped_report,
+    print_missing_classes, print_sampled_accuracy, render_report_template, rollup_misses_by_table,
+    sample_diff, score, to_format, write_json_lines, Average, ChurnStats, ClassScore, GroupBy,
+    HierarchyConflict, Reconciliation, ReportColumns, ReportField, ScoringReport, TaxonomyDiff,
+};
+pub use tree::{
+    ClassiError, ClassiErrorKind, ClassiNode, ClassiTree, ClassiVal, DiffResult, DiffUnit,
+    FieldMeta, MatchKind, MatchScope,
+};
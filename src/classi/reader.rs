@@ -0,0 +1,1795 @@
+//! 从Excel文档读取分类结果
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    fs,
+    io::{self, BufReader, Cursor, Read},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use calamine::{
+    open_workbook, open_workbook_from_rs, CellErrorType, Data, DataType, Range, Reader, Xlsx,
+    XlsxError,
+};
+use rayon::prelude::*;
+use unicode_normalization::UnicodeNormalization;
+
+use super::{
+    crypto::{decrypt_file_as, EncKey, FormatVersion},
+    tree::{ClassiError, ClassiErrorKind, ClassiTree, FieldMeta},
+};
+use crate::error::{self, SisyphusError};
+
+const CLASSI_SHEET: &str = "Sheet 1";
+pub(crate) const DB_HEADER: &str = "数据库名称";
+/// 表名列的表头名称，只用于`-v`时报告表头的识别结果，表名列总是数据库列之后的一列
+const TABLE_HEADERS: [&str; 2] = ["表名", "表名称"];
+/// 字段名列的表头名称，用法同`TABLE_HEADERS`
+const FIELD_HEADERS: [&str; 2] = ["字段名", "字段名称"];
+const MAX_DEPTH: usize = 12;
+/// 默认在表头的前多少列中查找数据库列
+const HEADER_COLUMNS: usize = 64;
+
+/// OLE复合文档的文件头，设置了打开密码的xlsx文件会被Excel加密保存为这种格式，而不是zip
+const CFB_MAGIC: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+/// 打开工作簿失败时，如果文件是加密的复合文档，给出需要去掉密码的提示
+fn check_protected<T>(res: Result<T, XlsxError>, head: &[u8]) -> anyhow::Result<T> {
+    let protected = head.starts_with(&CFB_MAGIC);
+    res.map_err(anyhow::Error::from).with_context(|| {
+        if protected {
+            String::from(
+                "workbook appears password-protected; please remove the Excel password before scoring",
+            )
+        } else {
+            String::from("failed to open the workbook")
+        }
+    })
+}
+
+pub fn new_workbook_from_file(file_path: &PathBuf) -> error::Result<Xlsx<BufReader<fs::File>>> {
+    let res: Result<Xlsx<_>, _> = open_workbook(file_path);
+    let mut head = [0u8; 8];
+    if res.is_err() {
+        let _ = fs::File::open(file_path).and_then(|mut f| f.read_exact(&mut head));
+    }
+    Ok(check_protected(res, &head)?)
+}
+
+pub fn new_workbook_from_bytes(bytes: &Vec<u8>) -> error::Result<Xlsx<Cursor<&Vec<u8>>>> {
+    let cursor = Cursor::new(bytes);
+    Ok(check_protected(open_workbook_from_rs(cursor), bytes)?)
+}
+
+/// 读取分类结果的配置
+///
+/// 默认值和不加任何命令行参数时的行为一致
+#[derive(Debug, Clone)]
+pub struct ParseConfig {
+    /// 分类结果所在的工作表，默认只有`Sheet 1`；有多个时并行读取各工作表再合并，
+    /// 同一字段出现在多个工作表中时按`dedupe`报错或者保留第一个工作表中的字段
+    pub sheet_names: Vec<String>,
+    /// 并行读取多个工作表的线程数量，0表示使用CPU核数，默认为0
+    pub sheet_jobs: usize,
+    /// 为真时，只指定了一个工作表并且该工作表读取失败或者没有字段，依次尝试工作簿中的其他工作表，
+    /// 使用第一个能读取出字段的工作表，默认为假
+    pub auto_sheet: bool,
+    /// 数据库列可以使用的表头名称，默认只有`数据库名称`
+    pub db_headers: Vec<String>,
+    /// 为`Some(n)`时表示文件没有表头，从第0行开始读取，前n列为分类级别，默认为`None`
+    pub class_levels: Option<usize>,
+    /// 为真时表头只需包含要查找的表头名称即可，例如`数据库名称(必填)`，适用于数据库、表名、字段名列，
+    /// 以及和模版、`required_headers`比较的分类列；默认为假，即表头必须完全相同
+    pub tolerant_header: bool,
+    /// 分类级别数量的下限，低于时报错，默认为1
+    pub min_levels: usize,
+    /// 分类级别数量的上限，超过时报错，默认为12
+    pub max_depth: usize,
+    /// 为真时Excel错误值（如`#N/A`）视为空单元格，否则跳过所在的行；
+    /// 为真时数据库名、表名或者字段名为空的行会被跳过，否则报错；默认为假。
+    /// 内容在字段列之前结束的缺列的行不受影响，总是被跳过
+    pub lenient: bool,
+    /// 为真时输出表头的识别结果以及跳过的行，默认为假
+    pub verbose: bool,
+    /// 读取加密文件时使用的格式版本，默认根据文件头自动识别
+    pub format_version: FormatVersion,
+    /// 解密加密文件使用的密钥，默认为内置的密钥
+    pub key: EncKey,
+    /// 数据库名、表名、字段名的规范化方式，默认不做处理
+    pub normalize: FieldNormalize,
+    /// 分类名称的规范化方式，默认不做处理
+    pub class_normalize: FieldNormalize,
+    /// 为`Some(sep)`时字段列的单元格可以包含以`sep`分隔的多个字段，拆分为同一分类路径下的多个字段，
+    /// 各部分去掉首尾空白，空的部分被忽略；默认为`None`
+    pub split_fields: Option<String>,
+    /// 为真时分类或者数据库名、表名、字段名中有不可见字符（控制字符、零宽空格等）时报错，
+    /// 否则给出警告；`normalize`包含`invisible`时字段中的不可见字符被去掉，默认为假
+    pub strict_unicode: bool,
+    /// 数据库名的重命名，键为原名称，值为新名称，在`normalize`之后应用，只影响数据库名，
+    /// 不影响表名和字段名；默认为空
+    pub db_map: BTreeMap<String, String>,
+    /// 为真时重复的字段只保留第一次出现的行并给出警告，否则报错，默认为假
+    pub dedupe: bool,
+    /// 为真时字段所在的分类路径也是字段身份的一部分，同一字段可以出现在不同的分类路径下，
+    /// 对比时各自作为独立的字段；默认为假，即同一字段只能出现一次
+    pub field_in_path: bool,
+    /// 遇到空行（所有单元格为空或者只有空白字符）时的处理方式，默认跳过空行继续读取
+    pub blank_row: BlankRow,
+    /// 分类结果文件的格式，为`None`时根据扩展名判断，`.csv`为CSV，其他为xlsx；
+    /// 从标准输入读取时没有扩展名，必须指定，默认为`None`
+    pub input_format: Option<InputFormat>,
+    /// 为`Some(n)`时跳过A列开始的n列，从第n+1列开始读取，默认为`None`，自动跳过左侧完全为空的列
+    pub col_offset: Option<usize>,
+    /// 为`Some(n)`时，出错或者被跳过的行在提示中附带该行以及前后各n行的原始内容，默认为`None`
+    pub context_lines: Option<usize>,
+    /// 模版中各分类级别的表头名称，按模版的顺序；分类结果的分类列顺序不同时按模版重新排列并给出警告，
+    /// 名称不同时报错；为`None`时不检查，默认为`None`
+    pub template_levels: Option<Vec<String>>,
+    /// 为`Some`时表头（去掉首尾空白以及右侧的空单元格）必须和列出的表头按顺序完全相同，否则在解析前报错，
+    /// 只在有表头时检查；默认为`None`
+    pub required_headers: Option<Vec<String>>,
+    /// 在表头的前多少列中查找数据库列，字段列之后的列全部忽略，默认为64
+    pub header_columns: usize,
+    /// 为`Some`时记录去重时丢弃的每个字段以及保留和丢弃的位置，默认为`None`
+    pub duplicate_log: Option<DuplicateLog>,
+}
+
+impl Default for ParseConfig {
+    fn default() -> Self {
+        Self {
+            sheet_names: vec![String::from(CLASSI_SHEET)],
+            sheet_jobs: 0,
+            auto_sheet: false,
+            db_headers: vec![String::from(DB_HEADER)],
+            class_levels: None,
+            tolerant_header: false,
+            min_levels: 1,
+            max_depth: MAX_DEPTH,
+            lenient: false,
+            verbose: false,
+            format_version: FormatVersion::Auto,
+            key: EncKey::built_in(),
+            normalize: FieldNormalize::default(),
+            class_normalize: FieldNormalize::default(),
+            split_fields: None,
+            strict_unicode: false,
+            db_map: BTreeMap::new(),
+            dedupe: false,
+            field_in_path: false,
+            blank_row: BlankRow::Skip,
+            input_format: None,
+            col_offset: None,
+            context_lines: None,
+            template_levels: None,
+            required_headers: None,
+            header_columns: HEADER_COLUMNS,
+            duplicate_log: None,
+        }
+    }
+}
+
+impl ParseConfig {
+    pub fn builder() -> ParseConfigBuilder {
+        ParseConfigBuilder::default()
+    }
+
+    /// 第一个工作表的名称
+    fn first_sheet(&self) -> &str {
+        self.sheet_names
+            .first()
+            .map_or(CLASSI_SHEET, String::as_str)
+    }
+
+    /// 表头是否为要查找的表头，`tolerant_header`为真时只需包含即可
+    fn header_matches(&self, head: &str, expected: &str) -> bool {
+        if self.tolerant_header {
+            head.contains(expected)
+        } else {
+            head == expected
+        }
+    }
+
+    /// 表头是否为数据库列
+    fn is_db_header(&self, head: &str) -> bool {
+        self.db_headers
+            .iter()
+            .any(|db_header| self.header_matches(head, db_header))
+    }
+}
+
+/// `ParseConfig`的构建器，没有设置的项使用默认值
+#[derive(Debug, Default)]
+pub struct ParseConfigBuilder {
+    config: ParseConfig,
+}
+
+impl ParseConfigBuilder {
+    pub fn sheet_name(mut self, sheet_name: impl Into<String>) -> Self {
+        self.config.sheet_names = vec![sheet_name.into()];
+        self
+    }
+
+    pub fn sheet_names(mut self, sheet_names: Vec<String>) -> Self {
+        self.config.sheet_names = sheet_names;
+        self
+    }
+
+    pub fn sheet_jobs(mut self, sheet_jobs: usize) -> Self {
+        self.config.sheet_jobs = sheet_jobs;
+        self
+    }
+
+    pub fn auto_sheet(mut self, auto_sheet: bool) -> Self {
+        self.config.auto_sheet = auto_sheet;
+        self
+    }
+
+    pub fn db_headers(mut self, db_headers: Vec<String>) -> Self {
+        self.config.db_headers = db_headers;
+        self
+    }
+
+    pub fn class_levels(mut self, class_levels: Option<usize>) -> Self {
+        self.config.class_levels = class_levels;
+        self
+    }
+
+    pub fn tolerant_header(mut self, tolerant_header: bool) -> Self {
+        self.config.tolerant_header = tolerant_header;
+        self
+    }
+
+    pub fn min_levels(mut self, min_levels: usize) -> Self {
+        self.config.min_levels = min_levels;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.config.max_depth = max_depth;
+        self
+    }
+
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.config.lenient = lenient;
+        self
+    }
+
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.config.verbose = verbose;
+        self
+    }
+
+    pub fn format_version(mut self, format_version: FormatVersion) -> Self {
+        self.config.format_version = format_version;
+        self
+    }
+
+    pub fn key(mut self, key: EncKey) -> Self {
+        self.config.key = key;
+        self
+    }
+
+    pub fn normalize(mut self, normalize: FieldNormalize) -> Self {
+        self.config.normalize = normalize;
+        self
+    }
+
+    pub fn class_normalize(mut self, class_normalize: FieldNormalize) -> Self {
+        self.config.class_normalize = class_normalize;
+        self
+    }
+
+    pub fn split_fields(mut self, split_fields: Option<String>) -> Self {
+        self.config.split_fields = split_fields;
+        self
+    }
+
+    pub fn strict_unicode(mut self, strict_unicode: bool) -> Self {
+        self.config.strict_unicode = strict_unicode;
+        self
+    }
+
+    pub fn db_map(mut self, db_map: BTreeMap<String, String>) -> Self {
+        self.config.db_map = db_map;
+        self
+    }
+
+    pub fn dedupe(mut self, dedupe: bool) -> Self {
+        self.config.dedupe = dedupe;
+        self
+    }
+
+    pub fn field_in_path(mut self, field_in_path: bool) -> Self {
+        self.config.field_in_path = field_in_path;
+        self
+    }
+
+    pub fn context_lines(mut self, context_lines: Option<usize>) -> Self {
+        self.config.context_lines = context_lines;
+        self
+    }
+
+    pub fn duplicate_log(mut self, duplicate_log: Option<DuplicateLog>) -> Self {
+        self.config.duplicate_log = duplicate_log;
+        self
+    }
+
+    pub fn required_headers(mut self, required_headers: Option<Vec<String>>) -> Self {
+        self.config.required_headers = required_headers;
+        self
+    }
+
+    pub fn header_columns(mut self, header_columns: usize) -> Self {
+        self.config.header_columns = header_columns;
+        self
+    }
+
+    pub fn template_levels(mut self, template_levels: Option<Vec<String>>) -> Self {
+        self.config.template_levels = template_levels;
+        self
+    }
+
+    pub fn blank_row(mut self, blank_row: BlankRow) -> Self {
+        self.config.blank_row = blank_row;
+        self
+    }
+
+    pub fn input_format(mut self, input_format: Option<InputFormat>) -> Self {
+        self.config.input_format = input_format;
+        self
+    }
+
+    pub fn col_offset(mut self, col_offset: Option<usize>) -> Self {
+        self.config.col_offset = col_offset;
+        self
+    }
+
+    pub fn build(self) -> ParseConfig {
+        self.config
+    }
+}
+
+/// 遇到空行时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlankRow {
+    /// 第一个空行结束读取，之后的行都被忽略
+    Stop,
+    /// 跳过空行继续读取，适用于用空行分隔各大类的文件
+    Skip,
+}
+
+impl FromStr for BlankRow {
+    type Err = SisyphusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stop" => Ok(BlankRow::Stop),
+            "skip" => Ok(BlankRow::Skip),
+            _ => Err(SisyphusError::InvalidOption(format!(
+                "unknown blank row handling [{}], expected stop or skip",
+                s
+            ))),
+        }
+    }
+}
+
+/// 去重时丢弃的一个字段，`kept`和`dropped`为保留和丢弃的字段所在的位置，如`row 12`或者文件名
+#[derive(Debug, Clone)]
+pub struct DroppedDuplicate {
+    pub path: String,
+    pub field: FieldMeta,
+    pub kept: String,
+    pub dropped: String,
+}
+
+/// 收集去重时丢弃的字段，克隆后共享同一份记录，可以在读取多个工作表的线程间共享
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateLog(Arc<Mutex<Vec<DroppedDuplicate>>>);
+
+impl DuplicateLog {
+    pub fn push(&self, duplicate: DroppedDuplicate) {
+        self.0.lock().unwrap().push(duplicate);
+    }
+
+    /// 取出目前记录的所有字段，按记录的顺序排列
+    pub fn take(&self) -> Vec<DroppedDuplicate> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+
+    /// 将记录的字段输出为CSV文件，列为database、table、field、path、kept、dropped，返回字段数量
+    pub fn write_csv(&self, path: &Path) -> error::Result<usize> {
+        let duplicates = self.take();
+        let mut wtr = csv::Writer::from_path(path).map_err(io::Error::from)?;
+        wtr.write_record(["database", "table", "field", "path", "kept", "dropped"])
+            .map_err(io::Error::from)?;
+        for dup in &duplicates {
+            wtr.write_record([
+                &dup.field.0,
+                &dup.field.1,
+                &dup.field.2,
+                &dup.path,
+                &dup.kept,
+                &dup.dropped,
+            ])
+            .map_err(io::Error::from)?;
+        }
+        wtr.flush()?;
+        Ok(duplicates.len())
+    }
+}
+
+/// 分类结果文件的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Xlsx,
+    /// 逗号分隔的CSV，各列和xlsx工作表相同
+    Csv,
+}
+
+impl FromStr for InputFormat {
+    type Err = SisyphusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "xlsx" => Ok(InputFormat::Xlsx),
+            "csv" => Ok(InputFormat::Csv),
+            _ => Err(SisyphusError::InvalidOption(format!(
+                "unknown input format [{}], expected xlsx or csv",
+                s
+            ))),
+        }
+    }
+}
+
+/// 数据库名、表名、字段名的规范化方式，解析时对三者做同样的处理
+///
+/// 字符串形式为逗号分隔的`trim`、`lower`、`collapse`、`unquote`、`nfc`、`invisible`，如`trim,lower`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FieldNormalize {
+    /// 去掉首尾的空白字符
+    pub trim: bool,
+    /// 转为小写
+    pub lower: bool,
+    /// 将连续的空白字符合并为一个空格
+    pub collapse: bool,
+    /// 去掉首尾的引号和反引号
+    pub unquote: bool,
+    /// 转为Unicode NFC规范形式
+    pub nfc: bool,
+    /// 去掉控制字符、零宽空格等不可见字符，见`invisible_char`
+    pub invisible: bool,
+}
+
+impl FieldNormalize {
+    pub fn apply(&self, s: &str) -> String {
+        let visible;
+        let mut res = s;
+        if self.invisible && invisible_char(s).is_some() {
+            visible = s.chars().filter(|&c| !is_invisible(c)).collect::<String>();
+            res = &visible;
+        }
+        if self.trim {
+            res = res.trim();
+        }
+        if self.unquote {
+            res = res.trim_matches(['"', '\'', '`']);
+            if self.trim {
+                res = res.trim();
+            }
+        }
+        let mut res = if self.collapse {
+            res.split_whitespace().collect::<Vec<_>>().join(" ")
+        } else {
+            res.to_string()
+        };
+        if self.nfc {
+            res = res.nfc().collect();
+        }
+        if self.lower {
+            res = res.to_lowercase();
+        }
+        res
+    }
+}
+
+/// 读取实际的数据库结构，即所有真实存在的字段，按`normalize`规范化后和分类结果中的字段比较
+///
+/// `.csv`文件每行为`数据库,表,字段`，第一行为`database,table,field`时作为表头跳过；
+/// 其他文件每行为`数据库.表.字段`，字段名中可以包含`.`，空行被忽略
+pub fn read_schema(path: &Path, normalize: &FieldNormalize) -> error::Result<HashSet<FieldMeta>> {
+    let field = |db: &str, table: &str, field: &str| {
+        FieldMeta(
+            normalize.apply(db),
+            normalize.apply(table),
+            normalize.apply(field),
+        )
+    };
+    let mut schema = HashSet::new();
+    if path.extension().is_some_and(|ext| ext == "csv") {
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_path(path)
+            .map_err(|e| SisyphusError::MalformedWorkbook(e.to_string()))?;
+        for (i, record) in rdr.records().enumerate() {
+            let record = record.map_err(|e| SisyphusError::MalformedWorkbook(e.to_string()))?;
+            match (record.get(0), record.get(1), record.get(2)) {
+                (Some("database"), Some("table"), Some("field")) if i == 0 => (),
+                (Some(db), Some(table), Some(name)) => {
+                    schema.insert(field(db, table, name));
+                }
+                _ => {
+                    return Err(SisyphusError::Other(format!(
+                        "line {} of the schema does not have the database, table and field columns",
+                        i + 1
+                    )))
+                }
+            }
+        }
+        return Ok(schema);
+    }
+
+    for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.splitn(3, '.').collect::<Vec<_>>()[..] {
+            [db, table, name] => {
+                schema.insert(field(db, table, name));
+            }
+            _ => {
+                return Err(SisyphusError::Other(format!(
+                    "line {} of the schema [{}] is not database.table.field",
+                    i + 1,
+                    line
+                )))
+            }
+        }
+    }
+    Ok(schema)
+}
+
+/// 分类树中不在实际数据库结构中的字段（不存在或者拼写错误的字段）以及字段所在的分类路径，
+/// 按字段排列
+pub fn phantom_fields<'a>(
+    tree: &'a ClassiTree,
+    schema: &HashSet<FieldMeta>,
+) -> Vec<(Vec<&'a str>, &'a FieldMeta)> {
+    let mut res: Vec<_> = tree
+        .field_paths()
+        .filter(|(_, field)| !schema.contains(*field))
+        .collect();
+    res.sort_by(|a, b| a.1.cmp(b.1).then_with(|| a.0.cmp(&b.0)));
+    res
+}
+
+/// 分类树中规范化后相同、原始形式不同的字段对，如只差首尾空格或者大小写的字段，
+/// 每组相近的字段中，按原始形式排序后第一个字段和其余各个字段各成一对
+pub fn near_duplicates<'a>(
+    tree: &'a ClassiTree,
+    normalize: &FieldNormalize,
+) -> Vec<(&'a FieldMeta, &'a FieldMeta)> {
+    let mut groups = BTreeMap::<FieldMeta, BTreeSet<&FieldMeta>>::new();
+    for (_, field) in tree.field_paths() {
+        let key = FieldMeta(
+            normalize.apply(&field.0),
+            normalize.apply(&field.1),
+            normalize.apply(&field.2),
+        );
+        groups.entry(key).or_default().insert(field);
+    }
+    groups
+        .into_values()
+        .filter(|fields| fields.len() > 1)
+        .flat_map(|fields| {
+            let mut fields = fields.into_iter();
+            let first = fields.next().unwrap();
+            fields.map(move |other| (first, other))
+        })
+        .collect()
+}
+
+impl FromStr for FieldNormalize {
+    type Err = SisyphusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut res = FieldNormalize::default();
+        for opt in s.split(',').map(str::trim).filter(|opt| !opt.is_empty()) {
+            match opt {
+                "trim" => res.trim = true,
+                "lower" => res.lower = true,
+                "collapse" => res.collapse = true,
+                "unquote" => res.unquote = true,
+                "nfc" => res.nfc = true,
+                "invisible" => res.invisible = true,
+                _ => {
+                    return Err(SisyphusError::InvalidOption(format!(
+                    "unknown normalization [{}], expected trim, lower, collapse, unquote, nfc or invisible",
+                    opt
+                )))
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl Display for FieldNormalize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let opts: Vec<&str> = [
+            (self.trim, "trim"),
+            (self.lower, "lower"),
+            (self.collapse, "collapse"),
+            (self.unquote, "unquote"),
+            (self.nfc, "nfc"),
+            (self.invisible, "invisible"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect();
+        write!(f, "{}", opts.join(","))
+    }
+}
+
+/// 是否为不可见字符：控制字符（包括换行和制表符）、零宽字符、双向文本控制字符、软连字符以及BOM，
+/// 这些字符通常是复制粘贴带入的，使看起来相同的名称无法匹配
+fn is_invisible(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\u{00AD}'
+                | '\u{180E}'
+                | '\u{200B}'..='\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2060}'..='\u{2064}'
+                | '\u{2066}'..='\u{2069}'
+                | '\u{FEFF}'
+        )
+}
+
+/// 字符串中第一个不可见字符及其字符位置（从1开始）
+fn invisible_char(s: &str) -> Option<(usize, char)> {
+    s.chars()
+        .enumerate()
+        .find(|(_, c)| is_invisible(*c))
+        .map(|(i, c)| (i + 1, c))
+}
+
+/// 根据表头确定分类级别的数量，即数据库列之前的列数
+fn count_classi_levels(headers: &[String], config: &ParseConfig) -> usize {
+    let classi_counter = headers
+        .iter()
+        .position(|head| config.is_db_header(head))
+        .unwrap_or(headers.len());
+
+    if config.verbose {
+        // 表名列和字段名列按位置确定，表头名称不符时仍然使用，只是说明不是按名称识别的
+        let by_name = |head: &str, names: &[&str]| {
+            if names
+                .iter()
+                .any(|name| config.header_matches(head.trim(), name))
+            {
+                ""
+            } else {
+                " by position"
+            }
+        };
+        for (i, head) in headers.iter().enumerate() {
+            let role = match i.checked_sub(classi_counter) {
+                None => format!("classification level {}", i),
+                Some(0) => String::from("database"),
+                Some(1) => format!("table{}", by_name(head, &TABLE_HEADERS)),
+                Some(2) => format!("field{}", by_name(head, &FIELD_HEADERS)),
+                Some(_) => String::from("unknown"),
+            };
+            eprintln!("header [{}] matched as {}", head, role);
+        }
+    }
+
+    classi_counter
+}
+
+/// 数据区域左侧完全为空的列数，calamine会去掉工作表左侧没有内容的列，
+/// 但是只有格式没有内容的单元格也会被计入数据区域
+fn leading_empty_columns(sheet: &Range<Data>) -> usize {
+    let (_, width) = sheet.get_size();
+    (0..width)
+        .take_while(|&col| sheet.rows().all(|row| row[col].is_empty()))
+        .count()
+}
+
+/// 确定数据从工作表的哪一列开始（从0开始），没有指定`col_offset`时自动跳过左侧的空列并给出警告
+fn first_data_column(sheet: &Range<Data>, config: &ParseConfig) -> u32 {
+    if let Some(offset) = config.col_offset {
+        return offset as u32;
+    }
+    let start_col = sheet.start().map_or(0, |(_, col)| col);
+    let offset = start_col + leading_empty_columns(sheet) as u32;
+    if offset > 0 {
+        eprintln!(
+            "warning: the first {} columns of the sheet are empty, reading data from column {}; use --col-offset to override",
+            offset,
+            offset + 1
+        );
+    }
+    offset
+}
+
+/// 错误提示中每个单元格最多显示的字符数量
+const CONTEXT_CELL_WIDTH: usize = 20;
+
+/// 数据区域中第`i`行以及前后各`lines`行的原始内容，每行一条，出错的行以`>`标记，
+/// `first_row`为数据区域第一行在工作表中的行号（从0开始）；过长的单元格被截断
+fn row_context(range: &Range<Data>, i: usize, lines: usize, first_row: u32) -> String {
+    range
+        .rows()
+        .enumerate()
+        .skip(i.saturating_sub(lines))
+        .take(i.min(lines) + lines + 1)
+        .map(|(j, row)| {
+            let cells = row
+                .iter()
+                .map(|cell| {
+                    let text = cell.to_string();
+                    if text.chars().count() > CONTEXT_CELL_WIDTH {
+                        let head: String = text.chars().take(CONTEXT_CELL_WIDTH - 1).collect();
+                        format!("{}…", head)
+                    } else {
+                        text
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" | ");
+            let marker = if j == i { '>' } else { ' ' };
+            format!("{} {:>5} | {}", marker, first_row as usize + j + 1, cells)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 读取单元格的文本内容
+///
+/// 单元格为Excel错误值（如`#N/A`、`#REF!`）时返回错误，`lenient`为真时视为空单元格。
+/// 布尔值按Excel的显示转为`TRUE`、`FALSE`；数字不带单元格格式，百分比格式的`25%`读取为`0.25`。
+/// 标准答案和分类结果使用相同的转换，同样的单元格总能得到同样的文本
+fn cell_text(cell: &Data, lenient: bool) -> Result<String, &CellErrorType> {
+    match cell {
+        Data::Error(_) if lenient => Ok(String::new()),
+        Data::Error(e) => Err(e),
+        Data::Bool(b) => Ok(String::from(if *b { "TRUE" } else { "FALSE" })),
+        cell => Ok(cell.to_string()),
+    }
+}
+
+/// 表头是否只是`class1`、`Column 2`这类没有含义的名称
+fn is_generic_header(head: &str) -> bool {
+    let head = head.trim().to_lowercase();
+    head.is_empty()
+        || ["class", "level", "column"].iter().any(|prefix| {
+            head.strip_prefix(prefix)
+                .is_some_and(|rest| rest.trim().chars().all(|c| c.is_ascii_digit()))
+        })
+}
+
+/// 读取分类结果，转化为分类树
+///
+/// 指定了多个工作表时，各工作表在线程池中并行解析，全部完成后再按工作表的顺序合并
+pub fn read_classi_result(
+    file_path: &PathBuf,
+    is_enc: bool,
+    config: &ParseConfig,
+) -> error::Result<ClassiTree> {
+    let (bytes, input_format) = read_input(file_path, is_enc, config)?;
+    read_classi_result_from_bytes(&bytes, input_format, config)
+}
+
+/// 从内存中的文件内容读取分类结果，转化为分类树，如上传的文件
+pub fn read_classi_result_from_bytes(
+    bytes: &Vec<u8>,
+    input_format: InputFormat,
+    config: &ParseConfig,
+) -> error::Result<ClassiTree> {
+    if input_format == InputFormat::Csv || config.sheet_names.len() <= 1 {
+        let tree = sheet_from_bytes(bytes, input_format, config.first_sheet())
+            .and_then(|sheet| Ok(build_tree_from_range(&sheet, config)?));
+        if !config.auto_sheet || input_format == InputFormat::Csv {
+            return Ok(tree?);
+        }
+        return match tree {
+            Ok(tree) if tree.fields().next().is_some() => Ok(tree),
+            tree => match other_sheet_tree(bytes, config) {
+                Some(other) => Ok(other),
+                None => Ok(tree?),
+            },
+        };
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config.sheet_jobs)
+        .build()
+        .map_err(|e| SisyphusError::Other(e.to_string()))?;
+    let trees = pool.install(|| {
+        config
+            .sheet_names
+            .par_iter()
+            .map(|sheet_name| {
+                let sheet = sheet_from_bytes(bytes, input_format, sheet_name)?;
+                let tree = build_tree_from_range(&sheet, config)
+                    .with_context(|| format!("failed to read the sheet [{}]", sheet_name))?;
+                Ok((sheet_name.clone(), tree))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+    })?;
+    Ok(merge_trees(
+        trees,
+        config.field_in_path,
+        !config.dedupe,
+        "sheet",
+        config.duplicate_log.as_ref(),
+    )?)
+}
+
+/// 依次尝试工作簿中指定工作表以外的其他工作表，返回第一个能读取出字段的分类树，都不能时返回`None`
+fn other_sheet_tree(bytes: &Vec<u8>, config: &ParseConfig) -> Option<ClassiTree> {
+    let first_sheet = config.first_sheet();
+    let sheet_names = new_workbook_from_bytes(bytes).ok()?.sheet_names();
+    sheet_names
+        .iter()
+        .filter(|sheet_name| sheet_name.as_str() != first_sheet)
+        .find_map(|sheet_name| {
+            let sheet = sheet_from_bytes(bytes, InputFormat::Xlsx, sheet_name).ok()?;
+            let tree = build_tree_from_range(&sheet, config).ok()?;
+            tree.fields().next()?;
+            eprintln!(
+                "warning: no fields were read from the sheet [{}], using the sheet [{}]",
+                first_sheet, sheet_name
+            );
+            Some(tree)
+        })
+}
+
+/// 读取多个分类结果文件，合并为一棵分类树，分类级别名称使用第一个文件的表头
+///
+/// 同一字段出现在多个文件中时，`strict`为真则列出所有重复的字段及其所在的两个文件并报错，
+/// 否则保留第一次读到的字段并给出警告
+pub fn read_classi_results(
+    files: &[PathBuf],
+    config: &ParseConfig,
+    strict: bool,
+) -> error::Result<ClassiTree> {
+    if let [file] = files {
+        return read_classi_result(file, false, config);
+    }
+
+    let trees = files
+        .iter()
+        .map(|file| {
+            let tree = read_classi_result(file, false, config)
+                .with_context(|| format!("failed to read [{}]", file.to_string_lossy()))?;
+            Ok((file.to_string_lossy().into_owned(), tree))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(merge_trees(
+        trees,
+        config.field_in_path,
+        strict,
+        "answer file",
+        config.duplicate_log.as_ref(),
+    )?)
+}
+
+/// 按顺序合并多个来源（文件或者工作表）的分类树，分类级别名称使用第一个分类树的
+///
+/// 同一字段出现在多个来源中时，`strict`为真则列出所有重复的字段及其所在的两个来源并报错，
+/// 否则保留第一次读到的字段并给出警告；`field_in_path`为真时分类路径不同的同一字段不算重复
+fn merge_trees(
+    trees: Vec<(String, ClassiTree)>,
+    field_in_path: bool,
+    strict: bool,
+    source_kind: &str,
+    duplicate_log: Option<&DuplicateLog>,
+) -> anyhow::Result<ClassiTree> {
+    let mut merged = ClassiTree::new();
+    let mut sources = HashMap::<(String, FieldMeta), &str>::new();
+    let mut duplicated = 0;
+    for (i, (source, tree)) in trees.iter().enumerate() {
+        if i == 0 {
+            merged.level_names = tree.level_names.clone();
+        }
+        for (classis, field) in tree.field_paths() {
+            let key = if field_in_path {
+                (classis.join("/"), field.clone())
+            } else {
+                (String::new(), field.clone())
+            };
+            if let Some(first) = sources.get(&key) {
+                if strict {
+                    eprintln!(
+                        "field [{}] appears in both [{}] and [{}]",
+                        field, first, source
+                    );
+                    duplicated += 1;
+                } else {
+                    eprintln!(
+                        "warning: field [{}] in [{}] was already read from [{}], keep the first",
+                        field, source, first
+                    );
+                    if let Some(log) = duplicate_log {
+                        log.push(DroppedDuplicate {
+                            path: classis.join("/"),
+                            field: field.clone(),
+                            kept: first.to_string(),
+                            dropped: source.clone(),
+                        });
+                    }
+                }
+                continue;
+            }
+            sources.insert(key, source);
+            merged.add_node(&classis, field.clone())?;
+        }
+    }
+
+    if duplicated > 0 {
+        return Err(
+            anyhow::Error::new(ClassiError::new(ClassiErrorKind::DuplicatedField)).context(
+                format!(
+                    "{} fields appear in more than one {}",
+                    duplicated, source_kind
+                ),
+            ),
+        );
+    }
+    Ok(merged)
+}
+
+/// 读取分类结果所在的第一个工作表的数据区域，`file_path`为`-`时从标准输入读取
+pub fn read_classi_sheet(
+    file_path: &PathBuf,
+    is_enc: bool,
+    config: &ParseConfig,
+) -> error::Result<Range<Data>> {
+    let (bytes, input_format) = read_input(file_path, is_enc, config)?;
+    Ok(sheet_from_bytes(
+        &bytes,
+        input_format,
+        config.first_sheet(),
+    )?)
+}
+
+/// 读取分类结果文件的全部内容以及文件格式，`file_path`为`-`时从标准输入读取，加密文件解密后为xlsx
+fn read_input(
+    file_path: &PathBuf,
+    is_enc: bool,
+    config: &ParseConfig,
+) -> anyhow::Result<(Vec<u8>, InputFormat)> {
+    if file_path.as_os_str() != "-" {
+        check_input_file(file_path)?;
+    }
+    if is_enc {
+        let bytes =
+            decrypt_file_as(file_path, config.format_version, &config.key).with_context(|| {
+                format!(
+                    "failed to decrypt the standard answer file [{}]",
+                    file_path.to_string_lossy()
+                )
+            })?;
+        return Ok((bytes, InputFormat::Xlsx));
+    }
+    if file_path.as_os_str() == "-" {
+        let input_format = config.input_format.ok_or_else(|| {
+            anyhow::Error::msg("reading the answer from stdin requires --input-format")
+        })?;
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes)?;
+        return Ok((bytes, input_format));
+    }
+    let input_format =
+        config
+            .input_format
+            .unwrap_or(match file_path.extension().and_then(|ext| ext.to_str()) {
+                Some("csv") => InputFormat::Csv,
+                _ => InputFormat::Xlsx,
+            });
+    let bytes = fs::read(file_path)
+        .with_context(|| format!("failed to read [{}]", file_path.to_string_lossy()))?;
+    Ok((bytes, input_format))
+}
+
+/// 检查输入文件存在、是普通文件并且不为空，路径错误或者上传失败时给出明确的提示，
+/// 而不是打开或者解析文件时的底层错误
+fn check_input_file(file_path: &Path) -> anyhow::Result<()> {
+    let path = file_path.to_string_lossy();
+    let metadata = match fs::metadata(file_path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(anyhow::Error::msg(format!(
+                "the file [{}] does not exist",
+                path
+            )));
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e).context(format!("failed to access [{}]", path)));
+        }
+    };
+    if !metadata.is_file() {
+        return Err(anyhow::Error::msg(format!(
+            "[{}] is not a regular file",
+            path
+        )));
+    }
+    if metadata.len() == 0 {
+        return Err(anyhow::Error::msg(format!("the file [{}] is empty", path)));
+    }
+    Ok(())
+}
+
+/// xlsx文件（zip压缩包）的文件头
+const ZIP_MAGIC: &[u8; 4] = b"PK\x03\x04";
+
+/// 端到端检查中的一个阶段，`result`为成功时的说明或者失败的原因
+pub struct ValidationStage {
+    pub name: &'static str,
+    pub result: Result<String, String>,
+}
+
+/// 对加密的标准答案做端到端的检查：能否解密、解密后是否为xlsx工作簿、是否有指定的工作表、
+/// 表头和内容能否构建分类树（包括重复字段的检查）
+///
+/// 按顺序检查，某个阶段失败后不再进行后续的阶段，用于在部署后的远程主机上验证加密文件
+pub fn validate_encrypted(file_path: &PathBuf, config: &ParseConfig) -> Vec<ValidationStage> {
+    let mut stages = Vec::new();
+    let mut stage = |name, result: Result<String, String>| {
+        let ok = result.is_ok();
+        stages.push(ValidationStage { name, result });
+        ok
+    };
+
+    if let Err(e) = check_input_file(file_path) {
+        stage("decrypt", Err(e.to_string()));
+        return stages;
+    }
+    let bytes = match decrypt_file_as(file_path, config.format_version, &config.key) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            stage("decrypt", Err(e.to_string()));
+            return stages;
+        }
+    };
+    if !stage("decrypt", Ok(format!("{} bytes", bytes.len()))) {
+        return stages;
+    }
+
+    let workbook = if bytes.starts_with(ZIP_MAGIC) {
+        new_workbook_from_bytes(&bytes).map_err(|e| e.to_string())
+    } else {
+        Err(String::from(
+            "the decrypted content is not an xlsx workbook",
+        ))
+    };
+    let mut workbook = match workbook {
+        Ok(workbook) => workbook,
+        Err(e) => {
+            stage("workbook", Err(e));
+            return stages;
+        }
+    };
+    stage("workbook", Ok(String::from("opened")));
+
+    let sheet_name = config.first_sheet();
+    let sheet = match workbook.worksheet_range(sheet_name) {
+        Ok(sheet) => sheet,
+        Err(e) => {
+            stage("sheet", Err(format!("[{}]: {}", sheet_name, e)));
+            return stages;
+        }
+    };
+    let (rows, cols) = sheet.get_size();
+    stage(
+        "sheet",
+        Ok(format!("[{}] {} rows, {} columns", sheet_name, rows, cols)),
+    );
+
+    let structure = build_tree_from_range(&sheet, config)
+        .map(|tree| {
+            // 通用表头的分类层级没有名称，层级数以字段最深的分类路径为准
+            let levels = tree
+                .field_paths()
+                .map(|(classis, _)| classis.len())
+                .max()
+                .unwrap_or(0);
+            format!(
+                "{} classification levels, {} fields",
+                levels,
+                tree.fields().count()
+            )
+        })
+        .map_err(|e| e.to_string());
+    stage("structure", structure);
+    stages
+}
+
+/// 将CSV内容转为和工作表相同的数据区域，空的单元格为`Data::Empty`
+pub fn range_from_csv<R: Read>(rdr: R) -> error::Result<Range<Data>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(rdr);
+    let records = rdr
+        .records()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| SisyphusError::MalformedWorkbook(e.to_string()))?;
+    let width = records.iter().map(|r| r.len()).max().unwrap_or(0);
+    if records.is_empty() || width == 0 {
+        return Ok(Range::empty());
+    }
+
+    let mut range = Range::new((0, 0), (records.len() as u32 - 1, width as u32 - 1));
+    for (i, record) in records.iter().enumerate() {
+        for (j, cell) in record
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| !cell.is_empty())
+        {
+            range.set_value((i as u32, j as u32), Data::String(cell.to_string()));
+        }
+    }
+    Ok(range)
+}
+
+/// 按指定的格式从内存中的内容读取数据区域，CSV没有工作表，忽略`sheet_name`
+fn sheet_from_bytes(
+    bytes: &Vec<u8>,
+    input_format: InputFormat,
+    sheet_name: &str,
+) -> anyhow::Result<Range<Data>> {
+    match input_format {
+        InputFormat::Csv => Ok(range_from_csv(bytes.as_slice())?),
+        InputFormat::Xlsx => {
+            let mut workbook = new_workbook_from_bytes(bytes)?;
+            workbook
+                .worksheet_range(sheet_name)
+                .with_context(|| format!("failed to open the sheet [{}]", sheet_name))
+        }
+    }
+}
+
+/// 读取表头中数据库列之前的各分类级别名称，并检查分类级别的数量
+fn header_levels(
+    sheet: &Range<Data>,
+    first_col: u32,
+    config: &ParseConfig,
+) -> anyhow::Result<Vec<String>> {
+    let start_row = sheet.start().map_or(0, |(row, _)| row);
+    // 只看前`header_columns`列，右侧远处零散的单元格不影响表头的识别
+    let last_col = sheet
+        .end()
+        .map_or(0, |(_, col)| col)
+        .min(first_col + config.header_columns.max(1) as u32 - 1)
+        .max(first_col);
+    let headers: Vec<String> = sheet
+        .range((start_row, first_col), (start_row, last_col))
+        .rows()
+        .next()
+        .filter(|_| !sheet.is_empty())
+        .ok_or(ClassiError::new(ClassiErrorKind::HeaderNotFound))?
+        .iter()
+        .map(|cell| cell.to_string())
+        .collect();
+    if let Some(required) = &config.required_headers {
+        check_required_headers(&headers, required, config)?;
+    }
+
+    let classi_counter = count_classi_levels(&headers, config);
+    if classi_counter == headers.len() {
+        return Err(
+            anyhow::Error::new(ClassiError::new(ClassiErrorKind::HeaderNotFound)).context(format!(
+                "no database column [{}] in the first {} columns of the header; use --header-columns to search further",
+                config.db_headers.join("], ["),
+                headers.len()
+            )),
+        );
+    }
+    if classi_counter > config.max_depth {
+        return Err(anyhow::Error::new(ClassiError::new(
+            ClassiErrorKind::TooManyLevels,
+        ))
+        .context(format!(
+            "{} classification levels detected before the database column, exceeding the maximum depth {}, headers: [{}]",
+            classi_counter,
+            config.max_depth,
+            headers[..classi_counter].join("], [")
+        )));
+    }
+
+    if classi_counter < config.min_levels {
+        return Err(anyhow::Error::new(ClassiError::new(
+            ClassiErrorKind::TooFewLevels,
+        ))
+        .context(format!(
+            "{} classification levels detected before the database column, fewer than the minimum {}; check that the database header is one of [{}]",
+            classi_counter,
+            config.min_levels,
+            config.db_headers.join("], [")
+        )));
+    }
+    if headers.len() < classi_counter + 3 {
+        return Err(
+            anyhow::Error::new(ClassiError::new(ClassiErrorKind::HeaderNotFound)).context(format!(
+                "expected the table and field columns after the database column, found {} columns from the database column",
+                headers.len().saturating_sub(classi_counter)
+            )),
+        );
+    }
+    let level_names = headers[..classi_counter]
+        .iter()
+        .map(|head| head.trim().to_string())
+        .collect::<Vec<_>>();
+    Ok(level_names)
+}
+
+/// 检查表头和要求的表头按顺序完全相同（`tolerant_header`为真时逐列包含即可），
+/// 不同时逐列列出要求的和实际的表头
+fn check_required_headers(
+    headers: &[String],
+    required: &[String],
+    config: &ParseConfig,
+) -> anyhow::Result<()> {
+    let mut actual: Vec<&str> = headers.iter().map(|head| head.trim()).collect();
+    while actual.last().is_some_and(|head| head.is_empty()) {
+        actual.pop();
+    }
+    let required: Vec<&str> = required.iter().map(|head| head.trim()).collect();
+    if actual.len() == required.len()
+        && actual
+            .iter()
+            .zip(&required)
+            .all(|(found, expected)| config.header_matches(found, expected))
+    {
+        return Ok(());
+    }
+
+    let diff: Vec<String> = (0..actual.len().max(required.len()))
+        .filter_map(|i| match (required.get(i), actual.get(i)) {
+            (Some(expected), Some(found)) if config.header_matches(found, expected) => None,
+            (Some(expected), Some(found)) => Some(format!(
+                "  column {}: expected [{}], found [{}]",
+                i + 1,
+                expected,
+                found
+            )),
+            (Some(expected), None) => Some(format!(
+                "  column {}: expected [{}], found nothing",
+                i + 1,
+                expected
+            )),
+            (None, Some(found)) => Some(format!("  column {}: unexpected [{}]", i + 1, found)),
+            (None, None) => None,
+        })
+        .collect();
+    Err(
+        anyhow::Error::new(ClassiError::new(ClassiErrorKind::HeaderMismatch)).context(format!(
+            "the header does not match the required headers\n  expected: [{}]\n  actual:   [{}]\n{}",
+            required.join("], ["),
+            actual.join("], ["),
+            diff.join("\n")
+        )),
+    )
+}
+
+/// 读取模版文件中各分类级别的表头名称，模版的格式和分类结果相同，可以没有数据行
+pub fn read_template_levels(
+    file_path: &PathBuf,
+    config: &ParseConfig,
+) -> error::Result<Vec<String>> {
+    let sheet = read_classi_sheet(file_path, false, config)?;
+    let levels =
+        header_levels(&sheet, first_data_column(&sheet, config), config).with_context(|| {
+            format!(
+                "failed to read the template [{}]",
+                file_path.to_string_lossy()
+            )
+        })?;
+    Ok(levels)
+}
+
+/// 分类结果中各分类列相对模版的顺序，第i项为模版第i级分类在分类结果中的列；
+/// 顺序和模版一致时返回`None`，表头名称和模版不同时报错，`tolerant_header`为真时表头包含模版的名称即可
+fn template_order(
+    level_names: &[String],
+    template: &[String],
+    config: &ParseConfig,
+) -> anyhow::Result<Option<Vec<usize>>> {
+    let mut used = vec![false; level_names.len()];
+    let order = template
+        .iter()
+        .map(|name| {
+            let col = (0..level_names.len())
+                .find(|&col| !used[col] && config.header_matches(&level_names[col], name))?;
+            used[col] = true;
+            Some(col)
+        })
+        .collect::<Option<Vec<_>>>()
+        .filter(|_| level_names.len() == template.len());
+    match order {
+        Some(order) if order.iter().enumerate().all(|(i, &col)| i == col) => Ok(None),
+        Some(order) => {
+            eprintln!(
+                "warning: the classification columns [{}] are ordered differently from the template [{}], reordered to match the template",
+                level_names.join("], ["),
+                template.join("], [")
+            );
+            Ok(Some(order))
+        }
+        None => Err(
+            anyhow::Error::new(ClassiError::new(ClassiErrorKind::TemplateMismatch)).context(
+                format!(
+                    "the classification columns [{}] do not match the template [{}]",
+                    level_names.join("], ["),
+                    template.join("], [")
+                ),
+            ),
+        ),
+    }
+}
+
+/// 从工作表的数据区域构建分类树
+///
+/// 缺列的行（从为空的数据库名、表名或者字段名到行尾都为空）以及有Excel错误值的行会被跳过并在最后汇总报告；
+/// 空行按`config.blank_row`跳过或者结束读取；第一列（最高级分类）为空但其他列有内容的行被跳过
+pub fn build_tree_from_range(
+    sheet: &Range<Data>,
+    config: &ParseConfig,
+) -> error::Result<ClassiTree> {
+    Ok(build_tree(sheet, config)?)
+}
+
+fn build_tree(sheet: &Range<Data>, config: &ParseConfig) -> anyhow::Result<ClassiTree> {
+    let verbose = config.verbose;
+    let start_row = sheet.start().map_or(0, |(row, _)| row);
+    let first_col = first_data_column(sheet, config);
+    let (classi_counter, first_row, level_names) =
+        match config.class_levels {
+            Some(levels) => {
+                if levels == 0 {
+                    return Err(ClassiError::new(ClassiErrorKind::LevelsMissing).into());
+                }
+                if levels < config.min_levels {
+                    return Err(anyhow::Error::new(ClassiError::new(
+                        ClassiErrorKind::TooFewLevels,
+                    ))
+                    .context(format!(
+                        "{} classification levels given, fewer than the minimum {}",
+                        levels, config.min_levels
+                    )));
+                }
+                if levels > config.max_depth {
+                    return Err(anyhow::Error::new(ClassiError::new(
+                        ClassiErrorKind::TooManyLevels,
+                    ))
+                    .context(format!(
+                        "{} classification levels given, exceeding the maximum depth {}",
+                        levels, config.max_depth
+                    )));
+                }
+                (levels, start_row, Vec::new())
+            }
+            None => {
+                let level_names = header_levels(sheet, first_col, config)?;
+                (level_names.len(), start_row + 1, level_names)
+            }
+        };
+
+    let last_row = sheet.end().map_or(0, |(row, _)| row);
+    let range = sheet.range(
+        (first_row, first_col),
+        (
+            last_row.max(first_row),
+            first_col + classi_counter as u32 + 2,
+        ),
+    );
+
+    let order = match &config.template_levels {
+        Some(template) if config.class_levels.is_none() => {
+            template_order(&level_names, template, config)?
+        }
+        _ => None,
+    };
+    let level_names = match &order {
+        Some(order) => order.iter().map(|&col| level_names[col].clone()).collect(),
+        None => level_names,
+    };
+
+    let mut tree = ClassiTree::new();
+    if !level_names.iter().any(|name| is_generic_header(name)) {
+        tree.level_names = level_names;
+    }
+    // 字段的身份及其第一次出现的行号，`field_in_path`为假时分类路径部分为空
+    let mut field_filter = HashMap::<(String, FieldMeta), usize>::new();
+
+    let context = |i: usize| {
+        config
+            .context_lines
+            .map(|lines| format!("\n{}", row_context(&range, i, lines, first_row)))
+            .unwrap_or_default()
+    };
+
+    let mut skipped_rows = Vec::new();
+    let mut duplicated = 0;
+    for (i, row) in range.rows().enumerate() {
+        // Excel中的行号从1开始
+        let row_no = first_row as usize + i + 1;
+        if row.iter().all(|cell| cell.to_string().trim().is_empty()) {
+            if config.blank_row == BlankRow::Stop {
+                if verbose {
+                    eprintln!("row {} is blank, stop reading", row_no);
+                }
+                break;
+            }
+            continue;
+        }
+        if row.first().unwrap().is_empty() {
+            if verbose {
+                eprintln!("row {} skipped: the first classification is empty", row_no);
+            }
+            continue;
+        }
+
+        let cells = match row
+            .iter()
+            .enumerate()
+            .map(|(col, cell)| cell_text(cell, config.lenient).map_err(|e| (col, e)))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(cells) => cells,
+            Err((col, e)) => {
+                eprintln!(
+                    "warning: row {} skipped: column {} contains the Excel error {}{}",
+                    row_no,
+                    col + 1,
+                    e,
+                    context(i)
+                );
+                skipped_rows.push(row_no);
+                continue;
+            }
+        };
+        // 数据区域固定为分类列加三列，行的长度总是相同的，内容在字段列之前结束的行才是缺列的行：
+        // 从第一个为空的数据库名、表名或者字段名开始到行尾都为空
+        let ids = &cells[classi_counter..classi_counter + 3];
+        if let Some(found) = ids.iter().position(|cell| cell.trim().is_empty()) {
+            if ids[found..].iter().all(|cell| cell.trim().is_empty()) {
+                eprintln!(
+                    "warning: row {} skipped: expected {} columns, found {}{}",
+                    row_no,
+                    classi_counter + 3,
+                    classi_counter + found,
+                    context(i)
+                );
+                skipped_rows.push(row_no);
+                continue;
+            }
+        }
+        for (col, cell) in cells.iter().enumerate().take(classi_counter + 3) {
+            let Some((pos, c)) = invisible_char(cell) else {
+                continue;
+            };
+            let msg = format!(
+                "row {}, column {}: [{}] contains the invisible character U+{:04X} at position {}",
+                row_no,
+                col + 1,
+                cell.escape_debug(),
+                c as u32,
+                pos
+            );
+            if config.strict_unicode {
+                return Err(anyhow::Error::new(ClassiError::new(
+                    ClassiErrorKind::InvisibleCharacter,
+                ))
+                .context(msg));
+            }
+            eprintln!("warning: {}", msg);
+        }
+
+        let lvls: Vec<String> = match &order {
+            Some(order) => order
+                .iter()
+                .map(|&col| config.class_normalize.apply(&cells[col]))
+                .collect(),
+            None => cells[..classi_counter]
+                .iter()
+                .map(|cell| config.class_normalize.apply(cell))
+                .collect(),
+        };
+        let lvls: Vec<&str> = lvls.iter().map(String::as_str).collect();
+        let db = config.normalize.apply(&cells[classi_counter]);
+        let db = config.db_map.get(&db).cloned().unwrap_or(db);
+        let table = config.normalize.apply(&cells[classi_counter + 1]);
+        let fields: Vec<String> = match &config.split_fields {
+            Some(sep) => cells[classi_counter + 2]
+                .split(sep.as_str())
+                .map(|field| config.normalize.apply(field.trim()))
+                .filter(|field| !field.is_empty())
+                .collect(),
+            None => vec![config.normalize.apply(&cells[classi_counter + 2])],
+        };
+        let first_field = fields.first().map_or("", String::as_str);
+        if let Some(component) = ["database", "table", "field"]
+            .into_iter()
+            .zip([db.as_str(), table.as_str(), first_field])
+            .find_map(|(name, val)| val.is_empty().then_some(name))
+        {
+            if !config.lenient {
+                return Err(
+                    anyhow::Error::new(ClassiError::new(ClassiErrorKind::EmptyIdentifier)).context(
+                        format!(
+                            "row {}: the {} name is empty{}",
+                            row_no,
+                            component,
+                            context(i)
+                        ),
+                    ),
+                );
+            }
+            eprintln!(
+                "warning: row {} skipped: the {} name is empty{}",
+                row_no,
+                component,
+                context(i)
+            );
+            skipped_rows.push(row_no);
+            continue;
+        }
+
+        // 拆分后的各个字段共用同一分类路径，分别检查是否重复
+        for field in fields {
+            let field_meta = FieldMeta(db.clone(), table.clone(), field);
+            let field_key = if config.field_in_path {
+                (lvls.join("/"), field_meta.clone())
+            } else {
+                (String::new(), field_meta.clone())
+            };
+            if let Some(&kept_row) = field_filter.get(&field_key) {
+                if !config.dedupe {
+                    return Err(anyhow::Error::new(ClassiError::new(
+                        ClassiErrorKind::DuplicatedField,
+                    ))
+                    .context(format!(
+                        "row {}: field [{}] is duplicated{}",
+                        row_no,
+                        field_meta,
+                        context(i)
+                    )));
+                }
+                if verbose {
+                    eprintln!(
+                        "row {} skipped: field [{}] is duplicated",
+                        row_no, field_meta
+                    );
+                }
+                if let Some(log) = &config.duplicate_log {
+                    log.push(DroppedDuplicate {
+                        path: lvls.join("/"),
+                        field: field_meta,
+                        kept: format!("row {}", kept_row),
+                        dropped: format!("row {}", row_no),
+                    });
+                }
+                duplicated += 1;
+                continue;
+            } else {
+                field_filter.insert(field_key, row_no);
+            }
+
+            tree.add_node(&lvls, field_meta)?;
+        }
+    }
+
+    if duplicated > 0 {
+        eprintln!("warning: dropped {} duplicated fields", duplicated);
+    }
+    if !skipped_rows.is_empty() {
+        eprintln!(
+            "warning: skipped {} unreadable rows: {}",
+            skipped_rows.len(),
+            skipped_rows
+                .iter()
+                .map(|row_no| row_no.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    Ok(tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 以CSV文本构造数据区域
+    fn sheet(csv: &str) -> Range<Data> {
+        range_from_csv(csv.as_bytes()).unwrap()
+    }
+
+    fn error_kind(e: &anyhow::Error) -> Option<ClassiErrorKind> {
+        e.downcast_ref::<ClassiError>().map(ClassiError::kind)
+    }
+
+    /// 读取失败时的错误，读取成功时测试失败
+    fn build_error(sheet: &Range<Data>, config: &ParseConfig) -> anyhow::Error {
+        match build_tree(sheet, config) {
+            Ok(_) => panic!("expected reading the sheet to fail"),
+            Err(e) => e,
+        }
+    }
+
+    const ANNOTATED: &str =
+        "一级分类(必填),二级分类(必填),数据库名称(必填),表名(必填),字段名(必填)\n\
+                             个人信息,身份信息,crm,user,name\n";
+
+    #[test]
+    fn tolerant_header_matches_annotated_headers() {
+        let strict = ParseConfig::default();
+        let e = build_error(&sheet(ANNOTATED), &strict);
+        assert_eq!(error_kind(&e), Some(ClassiErrorKind::HeaderNotFound));
+
+        let tolerant = ParseConfig::builder().tolerant_header(true).build();
+        let tree = build_tree(&sheet(ANNOTATED), &tolerant).unwrap();
+        let field = FieldMeta("crm".into(), "user".into(), "name".into());
+        assert_eq!(
+            tree.field_path(&field),
+            Some(&[String::from("个人信息"), String::from("身份信息")][..])
+        );
+    }
+
+    #[test]
+    fn tolerant_header_applies_to_template_and_required_headers() {
+        let template = vec![String::from("二级分类"), String::from("一级分类")];
+        let required: Vec<String> = ["一级分类", "二级分类", "数据库名称", "表名", "字段名"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        let strict = ParseConfig::builder()
+            .template_levels(Some(template.clone()))
+            .db_headers(vec![String::from("数据库名称(必填)")])
+            .build();
+        let e = build_error(&sheet(ANNOTATED), &strict);
+        assert_eq!(error_kind(&e), Some(ClassiErrorKind::TemplateMismatch));
+
+        let tolerant = ParseConfig::builder()
+            .tolerant_header(true)
+            .template_levels(Some(template))
+            .required_headers(Some(required.clone()))
+            .build();
+        let tree = build_tree(&sheet(ANNOTATED), &tolerant).unwrap();
+        let field = FieldMeta("crm".into(), "user".into(), "name".into());
+        assert_eq!(
+            tree.field_path(&field),
+            Some(&[String::from("身份信息"), String::from("个人信息")][..])
+        );
+
+        let strict = ParseConfig::builder()
+            .tolerant_header(false)
+            .required_headers(Some(required))
+            .build();
+        let e = build_error(&sheet(ANNOTATED), &strict);
+        assert_eq!(error_kind(&e), Some(ClassiErrorKind::HeaderMismatch));
+    }
+
+    #[test]
+    fn short_rows_are_skipped_and_reading_continues() {
+        let csv = "一级分类,二级分类,数据库名称,表名,字段名\n\
+                   个人信息,身份信息,crm,user,name\n\
+                   个人信息,身份信息,crm\n\
+                   个人信息,身份信息\n\
+                   个人信息,联系方式,crm,user,phone\n";
+        let tree = build_tree(&sheet(csv), &ParseConfig::default()).unwrap();
+        let mut fields: Vec<&str> = tree.fields().map(|fm| fm.2.as_str()).collect();
+        fields.sort();
+        assert_eq!(fields, ["name", "phone"]);
+    }
+
+    #[test]
+    fn empty_identifier_inside_a_row_is_still_an_error() {
+        let csv = "一级分类,数据库名称,表名,字段名\n\
+                   个人信息,crm,,name\n";
+        let e = build_error(&sheet(csv), &ParseConfig::default());
+        assert_eq!(error_kind(&e), Some(ClassiErrorKind::EmptyIdentifier));
+
+        let lenient = ParseConfig::builder().lenient(true).build();
+        let tree = build_tree(&sheet(csv), &lenient).unwrap();
+        assert_eq!(tree.fields().count(), 0);
+    }
+
+    #[test]
+    fn db_map_makes_renamed_databases_match() {
+        let solution = "一级分类,数据库名称,表名,字段名\n\
+                        个人信息,crm,user,name\n\
+                        个人信息,crm,user,phone\n";
+        let answer = "一级分类,数据库名称,表名,字段名\n\
+                      个人信息,crm_prod,user,name\n\
+                      个人信息,crm_prod,user,phone\n";
+        let solution = build_tree(&sheet(solution), &ParseConfig::default()).unwrap();
+        let existing = |config: &ParseConfig| {
+            let answer = build_tree(&sheet(answer), config).unwrap();
+            solution
+                .diff(&answer)
+                .iter()
+                .filter(|unit| unit.field_exist)
+                .count()
+        };
+
+        assert_eq!(existing(&ParseConfig::default()), 0);
+        let db_map = BTreeMap::from([(String::from("crm_prod"), String::from("crm"))]);
+        let mapped = ParseConfig::builder().db_map(db_map).build();
+        assert_eq!(existing(&mapped), 2);
+    }
+
+    #[test]
+    fn bool_cells_read_as_true_and_false() {
+        let mut range = Range::new((0, 0), (2, 3));
+        for (col, head) in ["一级分类", "数据库名称", "表名", "字段名"]
+            .into_iter()
+            .enumerate()
+        {
+            range.set_value((0, col as u32), Data::String(head.into()));
+        }
+        for (row, b) in [(1, true), (2, false)] {
+            range.set_value((row, 0), Data::Bool(b));
+            range.set_value((row, 1), Data::String("crm".into()));
+            range.set_value((row, 2), Data::String("flags".into()));
+            range.set_value((row, 3), Data::Bool(!b));
+        }
+        let config = ParseConfig::default();
+        let solution = build_tree_from_range(&range, &config).unwrap();
+        let answer = build_tree_from_range(&range, &config).unwrap();
+
+        let field = FieldMeta("crm".into(), "flags".into(), "FALSE".into());
+        assert_eq!(
+            solution.field_path(&field),
+            Some(&[String::from("TRUE")][..])
+        );
+        assert!(solution.diff(&answer).iter().all(|unit| unit.field_exist));
+
+        let csv = "一级分类,数据库名称,表名,字段名\n\
+                   TRUE,crm,flags,FALSE\n\
+                   FALSE,crm,flags,TRUE\n";
+        let typed = build_tree_from_range(&sheet(csv), &config).unwrap();
+        let diff = solution.diff(&typed);
+        assert_eq!(diff.len(), 2);
+        assert!(diff.iter().all(|unit| unit.field_exist));
+    }
+
+    #[test]
+    fn required_headers_match_exactly() {
+        let headers = |list: &[&str]| {
+            list.iter()
+                .map(|&head| String::from(head))
+                .collect::<Vec<_>>()
+        };
+        let required = headers(&["一级分类", "数据库名称", "表名", "字段名"]);
+        let config = ParseConfig::default();
+
+        let found = headers(&["一级分类", " 数据库名称", "表名 ", "字段名", ""]);
+        check_required_headers(&found, &required, &config).unwrap();
+
+        let found = headers(&["一级分类", "表名", "数据库名称"]);
+        let e = check_required_headers(&found, &required, &config).unwrap_err();
+        assert_eq!(error_kind(&e), Some(ClassiErrorKind::HeaderMismatch));
+        assert_eq!(
+            e.to_string(),
+            "the header does not match the required headers\n  \
+             expected: [一级分类], [数据库名称], [表名], [字段名]\n  \
+             actual:   [一级分类], [表名], [数据库名称]\n  \
+             column 2: expected [数据库名称], found [表名]\n  \
+             column 3: expected [表名], found [数据库名称]\n  \
+             column 4: expected [字段名], found nothing"
+        );
+    }
+}
@@ -0,0 +1,1674 @@
+//! 分类成绩的统计和输出
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use rand::{seq::index, Rng};
+use rust_xlsxwriter::Workbook;
+use serde::{Deserialize, Serialize};
+use tinytemplate::TinyTemplate;
+
+use super::stats::{percentile_desc, wilson_interval};
+use super::text::{display_width, fit_to_width};
+use super::tree::{ClassiTree, DiffResult, DiffUnit, FieldMeta, MatchKind};
+use super::writer::write_atomic;
+use crate::error::SisyphusError;
+
+/// 分类成绩，由对比结果汇总得到
+///
+/// 可以从保存的JSON读回，较早版本保存的成绩中没有的字段使用默认值
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ScoringReport {
+    pub total: i32,
+    pub matched: i32,
+    /// 分类路径是标准答案分类路径前缀的字段数量，不计入`matched`
+    #[serde(default)]
+    pub partial: i32,
+    /// 总的正确率，按`average`指定的方式计算
+    pub overall: f64,
+    #[serde(default)]
+    pub average: Average,
+    /// 所有字段合在一起的正确率，字段多的大类影响更大
+    #[serde(default)]
+    pub micro_accuracy: f64,
+    /// 各大类正确率的平均值，每个大类的权重相同，不包括没有字段的大类
+    #[serde(default)]
+    pub macro_accuracy: f64,
+    /// 各大类的成绩，按大类名称排列，保证相同的输入得到相同的输出
+    pub per_class: Vec<ClassScore>,
+}
+
+/// `--output`输出的JSON格式的成绩，`groups`为各大类的`[总数, 匹配数]`
+#[derive(Serialize)]
+struct ReportOutput<'a> {
+    total: i32,
+    matched: i32,
+    overall: f64,
+    groups: BTreeMap<&'a str, (i32, i32)>,
+    units: &'a DiffResult,
+}
+
+/// 某一大类下的分类成绩
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ClassScore {
+    pub class: String,
+    pub total: i32,
+    pub matched: i32,
+    pub accuracy: f64,
+}
+
+/// 总的正确率的平均方式
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Average {
+    /// 所有字段合在一起计算
+    #[default]
+    Micro,
+    /// 各大类的正确率取平均
+    Macro,
+}
+
+impl FromStr for Average {
+    type Err = SisyphusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "micro" => Ok(Average::Micro),
+            "macro" => Ok(Average::Macro),
+            _ => Err(SisyphusError::InvalidOption(format!(
+                "unknown average '{}', expected micro or macro",
+                s
+            ))),
+        }
+    }
+}
+
+fn count_partial(r: &DiffResult) -> i32 {
+    r.iter()
+        .filter(|unit| unit.match_kind == MatchKind::Partial)
+        .count() as i32
+}
+
+/// 各分组正确率的平均值，跳过没有字段的分组，没有任何分组时为0
+fn macro_accuracy(per_class: &[ClassScore]) -> f64 {
+    let accuracies: Vec<f64> = per_class
+        .iter()
+        .filter(|class| class.total > 0)
+        .map(|class| class.accuracy)
+        .collect();
+    if accuracies.is_empty() {
+        return 0.0;
+    }
+    accuracies.iter().sum::<f64>() / accuracies.len() as f64
+}
+
+/// 匹配数占总数的比例，总数为0时为0
+fn ratio(matched: i32, total: i32) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    matched as f64 / total as f64
+}
+
+/// 按第一级分类汇总，没有分类路径的字段计入名称为空的大类；总数为0时各正确率为0
+impl From<&DiffResult> for ScoringReport {
+    fn from(r: &DiffResult) -> Self {
+        let total = r.len() as i32;
+        let mut match_classi = 0;
+        let mut group_statistic = BTreeMap::<String, (i32, i32)>::new();
+        for unit in r {
+            let first_classi = unit.classis.first().cloned().unwrap_or_default();
+            let cal_u = if unit.field_exist { 1 } else { 0 };
+            match_classi += cal_u;
+            group_statistic
+                .entry(first_classi)
+                .and_modify(|e| {
+                    e.0 += 1;
+                    e.1 += cal_u;
+                })
+                .or_insert((1, cal_u));
+        }
+
+        let per_class = group_statistic
+            .into_iter()
+            .map(|(class, (total, matched))| ClassScore {
+                class,
+                total,
+                matched,
+                accuracy: ratio(matched, total),
+            })
+            .collect::<Vec<_>>();
+
+        let overall = ratio(match_classi, total);
+        Self {
+            total,
+            matched: match_classi,
+            partial: count_partial(r),
+            overall,
+            average: Average::Micro,
+            micro_accuracy: overall,
+            macro_accuracy: macro_accuracy(&per_class),
+            per_class,
+        }
+    }
+}
+
+/// 重新汇总对比结果时的分组方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    /// 按前N级分类路径分组
+    Class(usize),
+    /// 按数据库分组
+    Database,
+    /// 按表分组
+    Table,
+}
+
+impl FromStr for GroupBy {
+    type Err = SisyphusError;
+
+    /// 可以是`class`、`class:N`、`database`或者`table`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "class" => Ok(GroupBy::Class(1)),
+            None if s == "database" => Ok(GroupBy::Database),
+            None if s == "table" => Ok(GroupBy::Table),
+            Some(("class", depth)) => match depth.parse::<usize>() {
+                Ok(depth) if depth > 0 => Ok(GroupBy::Class(depth)),
+                _ => Err(SisyphusError::InvalidOption(format!(
+                    "invalid class depth '{}', expected a positive integer",
+                    depth
+                ))),
+            },
+            _ => Err(SisyphusError::InvalidOption(format!(
+                "unknown group '{}', expected class, class:N, database or table",
+                s
+            ))),
+        }
+    }
+}
+
+impl GroupBy {
+    fn key(&self, unit: &DiffUnit) -> String {
+        match self {
+            GroupBy::Class(depth) => unit
+                .classis
+                .iter()
+                .take(*depth)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("/"),
+            GroupBy::Database => unit.field.0.clone(),
+            GroupBy::Table => format!("{}-{}", unit.field.0, unit.field.1),
+        }
+    }
+}
+
+impl ScoringReport {
+    /// 正确率低于`threshold`的大类，不包括没有字段的大类
+    pub fn classes_below(&self, threshold: f64) -> Vec<&ClassScore> {
+        self.per_class
+            .iter()
+            .filter(|class| class.total > 0 && class.accuracy < threshold)
+            .collect()
+    }
+
+    /// 按指定的方式计算总的正确率
+    pub fn set_average(&mut self, average: Average) {
+        self.average = average;
+        self.overall = match average {
+            Average::Micro => self.micro_accuracy,
+            Average::Macro => self.macro_accuracy,
+        };
+    }
+
+    /// 加入没有任何字段的大类，这些大类的成绩为0/0，正确率记为0
+    pub fn include_empty_classes(&mut self, classes: Vec<String>) {
+        for class in classes {
+            if !self.per_class.iter().any(|c| c.class == class) {
+                self.per_class.push(ClassScore {
+                    class,
+                    ..Default::default()
+                });
+            }
+        }
+        self.per_class.sort_by(|a, b| a.class.cmp(&b.class));
+    }
+
+    /// 输出各大类的成绩汇总CSV，列为`class,total,matched,accuracy`，按大类名称排列
+    pub fn write_summary_csv(&self, path: &PathBuf) -> anyhow::Result<()> {
+        let mut classes: Vec<&ClassScore> = self.per_class.iter().collect();
+        classes.sort_by(|a, b| a.class.cmp(&b.class));
+
+        let mut wtr = csv::Writer::from_path(path)?;
+        wtr.write_record(["class", "total", "matched", "accuracy"])?;
+        for class in classes {
+            wtr.write_record([
+                class.class.clone(),
+                class.total.to_string(),
+                class.matched.to_string(),
+                format!("{:.4}", class.accuracy),
+            ])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// 将对比结果和成绩汇总写入文件，`format`为`json`或者`csv`
+    ///
+    /// `json`为一个对象，包含总数、匹配数、总的正确率、各大类的`[总数, 匹配数]`以及逐字段的对比结果；
+    /// `csv`每个字段一行，列为`path,field,matched`，分类路径以`/`连接，字段为`db-table-field`
+    pub fn write_output(&self, r: &DiffResult, path: &Path, format: &str) -> anyhow::Result<()> {
+        match format {
+            "json" => {
+                let output = ReportOutput {
+                    total: self.total,
+                    matched: self.matched,
+                    overall: self.overall,
+                    groups: self
+                        .per_class
+                        .iter()
+                        .map(|class| (class.class.as_str(), (class.total, class.matched)))
+                        .collect(),
+                    units: r,
+                };
+                let mut w = io::BufWriter::new(fs::File::create(path)?);
+                serde_json::to_writer_pretty(&mut w, &output)?;
+                writeln!(w)?;
+                w.flush()?;
+            }
+            "csv" => {
+                let mut wtr = csv::Writer::from_path(path)?;
+                wtr.write_record(["path", "field", "matched"])?;
+                for unit in r {
+                    wtr.write_record([
+                        unit.classis.join("/"),
+                        unit.field.to_string(),
+                        unit.field_exist.to_string(),
+                    ])?;
+                }
+                wtr.flush()?;
+            }
+            _ => {
+                return Err(anyhow::Error::msg(format!(
+                    "unsupported output format [{}], expected json or csv",
+                    format
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// 将成绩汇总、各大类成绩和逐字段的对比结果分别写入同一个.xlsx文件的`summary`、`classes`、
+    /// `fields`三个工作表，`fields`的列由`columns`指定
+    ///
+    /// 工作簿完整生成后再通过`write_atomic`写入，不会用不完整的文件替换已有的报告
+    pub fn write_xlsx(
+        &self,
+        r: &DiffResult,
+        columns: &ReportColumns,
+        path: &Path,
+    ) -> anyhow::Result<()> {
+        let mut workbook = Workbook::new();
+
+        let summary = workbook.add_worksheet().set_name("summary")?;
+        for (i, (name, value)) in [
+            ("total", self.total as f64),
+            ("matched", self.matched as f64),
+            ("accuracy", self.overall),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            summary.write_string(i as u32, 0, name)?;
+            summary.write_number(i as u32, 1, value)?;
+        }
+
+        let classes = workbook.add_worksheet().set_name("classes")?;
+        for (j, name) in ["class", "total", "matched", "accuracy"]
+            .into_iter()
+            .enumerate()
+        {
+            classes.write_string(0, j as u16, name)?;
+        }
+        for (i, class) in self.per_class.iter().enumerate() {
+            let row = i as u32 + 1;
+            classes.write_string(row, 0, &class.class)?;
+            classes.write_number(row, 1, class.total as f64)?;
+            classes.write_number(row, 2, class.matched as f64)?;
+            classes.write_number(row, 3, class.accuracy)?;
+        }
+
+        let fields = workbook.add_worksheet().set_name("fields")?;
+        for (j, name) in columns.header().into_iter().enumerate() {
+            fields.write_string(0, j as u16, name)?;
+        }
+        for (i, unit) in r.iter().enumerate() {
+            for (j, cell) in columns.row(unit).iter().enumerate() {
+                fields.write_string(i as u32 + 1, j as u16, cell)?;
+            }
+        }
+
+        write_atomic(path, &workbook.save_to_buffer()?)?;
+        Ok(())
+    }
+
+    /// 合并多次打分的成绩，如分别对各个数据库打分的结果，总数和匹配数相加后重新计算正确率，
+    /// 同名的大类合并为一个，结果按大类名称排列，总的正确率为微平均
+    pub fn merge(reports: &[ScoringReport]) -> Self {
+        let mut per_class = BTreeMap::<&str, (i32, i32)>::new();
+        for class in reports.iter().flat_map(|report| &report.per_class) {
+            let e = per_class.entry(&class.class).or_insert((0, 0));
+            e.0 += class.total;
+            e.1 += class.matched;
+        }
+        let per_class: Vec<ClassScore> = per_class
+            .into_iter()
+            .map(|(class, (total, matched))| ClassScore {
+                class: class.to_string(),
+                total,
+                matched,
+                accuracy: ratio(matched, total),
+            })
+            .collect();
+
+        let total = reports.iter().map(|report| report.total).sum::<i32>();
+        let matched = reports.iter().map(|report| report.matched).sum::<i32>();
+        let overall = ratio(matched, total);
+        Self {
+            total,
+            matched,
+            partial: reports.iter().map(|report| report.partial).sum(),
+            overall,
+            average: Average::Micro,
+            micro_accuracy: overall,
+            macro_accuracy: macro_accuracy(&per_class),
+            per_class,
+        }
+    }
+
+    /// 按指定方式分组汇总对比结果，`per_class`按正确率从低到高排列，正确率相同时按名称排列
+    pub fn grouped(r: &DiffResult, group_by: GroupBy) -> Self {
+        let mut group_statistic = HashMap::<String, (i32, i32)>::new();
+        for unit in r {
+            let e = group_statistic.entry(group_by.key(unit)).or_insert((0, 0));
+            e.0 += 1;
+            if unit.field_exist {
+                e.1 += 1;
+            }
+        }
+
+        let mut per_class: Vec<ClassScore> = group_statistic
+            .into_iter()
+            .map(|(class, (total, matched))| ClassScore {
+                class,
+                total,
+                matched,
+                accuracy: ratio(matched, total),
+            })
+            .collect();
+        per_class.sort_by(|a, b| {
+            a.accuracy
+                .total_cmp(&b.accuracy)
+                .then_with(|| a.class.cmp(&b.class))
+        });
+
+        let matched = r.iter().filter(|unit| unit.field_exist).count() as i32;
+        let overall = ratio(matched, r.len() as i32);
+        Self {
+            total: r.len() as i32,
+            matched,
+            partial: count_partial(r),
+            overall,
+            average: Average::Micro,
+            micro_accuracy: overall,
+            macro_accuracy: macro_accuracy(&per_class),
+            per_class,
+        }
+    }
+}
+
+/// 对比标准答案和分类结果，计算分类成绩
+pub fn score(solution: &ClassiTree, answer: &ClassiTree) -> ScoringReport {
+    ScoringReport::from(&solution.diff(answer))
+}
+
+/// 输出分类成绩，`report`由`r`汇总得到，可能另外包含没有字段的大类
+///
+/// `width`为`Some`时各大类的成绩按该宽度排版，宽度足够时带有正确率的进度条，
+/// 为`None`时每个大类输出一行`classification [名称] accuracy: 93.00%`。`explain`为`Some(n)`时，
+/// 总的正确率后附带计算所用的分子分母，每个大类后列出最多n个未匹配的字段。
+/// `threshold`为`Some`时正确率低于它的大类以`!`标记，并在最后单独列出
+pub fn claussi_report(
+    r: &DiffResult,
+    report: &ScoringReport,
+    width: Option<usize>,
+    explain: Option<usize>,
+    threshold: Option<f64>,
+) -> anyhow::Result<()> {
+    let classes = report.per_class.iter().filter(|c| c.total > 0).count();
+    let micro_inputs = format!(" = {}/{}", report.matched, report.total);
+    let macro_inputs = format!(" = mean of {} class accuracies", classes);
+    let (micro_inputs, macro_inputs) = match explain {
+        Some(_) => (micro_inputs.as_str(), macro_inputs.as_str()),
+        None => ("", ""),
+    };
+    println!(
+        "total classification accuracy: {:.2}%{}",
+        report.overall * 100f64,
+        match report.average {
+            Average::Micro => micro_inputs,
+            Average::Macro => macro_inputs,
+        }
+    );
+    match report.average {
+        Average::Micro => println!(
+            "macro average over classes: {:.2}%{}",
+            report.macro_accuracy * 100f64,
+            macro_inputs
+        ),
+        Average::Macro => println!(
+            "(macro average over classes, micro average over fields: {:.2}%{})",
+            report.micro_accuracy * 100f64,
+            micro_inputs
+        ),
+    }
+    if report.partial > 0 {
+        println!(
+            "partial (prefix) matches, counted as missing: {}",
+            report.partial
+        );
+    }
+
+    let Some(examples) = explain else {
+        for line in class_lines(report, width, threshold) {
+            println!("{}", line);
+        }
+        print_classes_below(report, threshold);
+        return Ok(());
+    };
+
+    let mut misses = HashMap::<&str, Vec<&FieldMeta>>::new();
+    for unit in r.iter().filter(|unit| !unit.field_exist) {
+        misses
+            .entry(unit.classis.first().map_or("", String::as_str))
+            .or_default()
+            .push(&unit.field);
+    }
+    for (line, class) in class_lines(report, width, threshold)
+        .iter()
+        .zip(&report.per_class)
+    {
+        println!("{}", line);
+        let fields = misses
+            .get(class.class.as_str())
+            .map_or(&[][..], Vec::as_slice);
+        if fields.is_empty() || examples == 0 {
+            continue;
+        }
+        let shown: Vec<String> = fields
+            .iter()
+            .take(examples)
+            .map(|f| f.to_string())
+            .collect();
+        let more = match fields.len().saturating_sub(examples) {
+            0 => String::new(),
+            n => format!(" (+{} more)", n),
+        };
+        println!("    missing: {}{}", shown.join(", "), more);
+    }
+    print_classes_below(report, threshold);
+
+    Ok(())
+}
+
+/// 单独列出正确率低于`threshold`的大类，没有时不输出
+fn print_classes_below(report: &ScoringReport, threshold: Option<f64>) {
+    let Some(threshold) = threshold else {
+        return;
+    };
+    let below = report.classes_below(threshold);
+    if below.is_empty() {
+        return;
+    }
+    println!(
+        "classes below {:.2}% ({}):",
+        threshold * 100f64,
+        below.len()
+    );
+    for class in below {
+        println!(
+            "  {}: {:.2}% ({}/{})",
+            class.class,
+            class.accuracy * 100f64,
+            class.matched,
+            class.total
+        );
+    }
+}
+
+/// 各大类成绩的各行，`width`为`None`时为`classification [名称] accuracy: 93.00%`，否则见`bar_lines`；
+/// `threshold`为`Some`时每行前有两列标记，正确率低于它的大类为`! `，其他为空格
+fn class_lines(
+    report: &ScoringReport,
+    width: Option<usize>,
+    threshold: Option<f64>,
+) -> Vec<String> {
+    if let Some(width) = width {
+        return bar_lines(report, width, threshold);
+    }
+    report
+        .per_class
+        .iter()
+        .map(|class| {
+            let mark = match threshold {
+                Some(t) if class.total > 0 && class.accuracy < t => "! ",
+                Some(_) => "  ",
+                None => "",
+            };
+            if class.total == 0 {
+                format!(
+                    "{}classification [{}] has no fields (0/0)",
+                    mark, class.class
+                )
+            } else {
+                format!(
+                    "{}classification [{}] accuracy: {:.2}%",
+                    mark,
+                    class.class,
+                    class.accuracy * 100f64
+                )
+            }
+        })
+        .collect()
+}
+
+/// 最短的进度条宽度，剩余宽度不够时不显示进度条
+const MIN_BAR_WIDTH: usize = 10;
+
+/// 按`width`排版的各大类成绩，每行为`名称  进度条  正确率 (匹配数/总数)`，总宽度不超过`width`
+///
+/// 名称最多占三分之一的宽度，过长时按显示宽度截断。`threshold`为`Some`时每行前有两列标记，
+/// 正确率低于它的大类为`! `，其他为空格
+fn bar_lines(report: &ScoringReport, width: usize, threshold: Option<f64>) -> Vec<String> {
+    let marks: Vec<&str> = report
+        .per_class
+        .iter()
+        .map(|class| match threshold {
+            Some(t) if class.total > 0 && class.accuracy < t => "! ",
+            Some(_) => "  ",
+            None => "",
+        })
+        .collect();
+    let width = width.saturating_sub(if threshold.is_some() { 2 } else { 0 });
+    let stats: Vec<String> = report
+        .per_class
+        .iter()
+        .map(|class| {
+            if class.total == 0 {
+                String::from("no fields (0/0)")
+            } else {
+                format!(
+                    "{:>6.2}% ({}/{})",
+                    class.accuracy * 100f64,
+                    class.matched,
+                    class.total
+                )
+            }
+        })
+        .collect();
+    let stats_width = stats.iter().map(|s| s.len()).max().unwrap_or(0);
+    let name_width = report
+        .per_class
+        .iter()
+        .map(|class| display_width(&class.class))
+        .max()
+        .unwrap_or(0)
+        .min((width / 3).max(8));
+    let bar_width = width.saturating_sub(name_width + stats_width + 4);
+
+    report
+        .per_class
+        .iter()
+        .zip(stats)
+        .zip(marks)
+        .map(|((class, stats), mark)| {
+            let name = fit_to_width(&class.class, name_width);
+            if bar_width < MIN_BAR_WIDTH {
+                return format!("{}{}  {}", mark, name, stats);
+            }
+            let filled = (class.accuracy * bar_width as f64).round() as usize;
+            let bar = if class.total == 0 {
+                " ".repeat(bar_width)
+            } else {
+                "#".repeat(filled) + &"-".repeat(bar_width - filled)
+            };
+            format!("{}{}  {}  {}", mark, name, bar, stats)
+        })
+        .collect()
+}
+
+/// 完全没有匹配字段的分类，`depth`为分类路径的级数，1表示大类
+///
+/// 只统计对比结果中出现的分类，没有任何字段的分类（0/0）不会列出，和`--quiet-empty-classes`无关
+pub fn missing_classes(r: &DiffResult, depth: usize) -> Vec<ClassScore> {
+    ScoringReport::grouped(r, GroupBy::Class(depth))
+        .per_class
+        .into_iter()
+        .filter(|class| class.matched == 0)
+        .collect()
+}
+
+/// 输出完全没有匹配字段的分类以及各自的字段数量，字段多的在前
+pub fn print_missing_classes(r: &DiffResult, depth: usize) {
+    let mut classes = missing_classes(r, depth);
+    classes.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.class.cmp(&b.class)));
+    println!("completely missing categories ({})", classes.len());
+    for class in &classes {
+        println!("  {}: {} fields", class.class, class.total);
+    }
+}
+
+/// 输出每个字段匹配的分类级数的分布，如`depth matched: p50=3 p90=2 p99=1`
+///
+/// `pN`表示N%的字段匹配的分类级数不少于该值
+pub fn print_depth_percentiles(solution: &ClassiTree, answer: &ClassiTree) {
+    let mut depths = solution.matched_depths(answer);
+    depths.sort_unstable_by(|a, b| b.cmp(a));
+    let percentiles: Vec<String> = [50, 90, 99]
+        .into_iter()
+        .filter_map(|p| percentile_desc(&depths, p as f64 / 100.0).map(|d| format!("p{}={}", p, d)))
+        .collect();
+    println!("depth matched: {}", percentiles.join(" "));
+}
+
+/// 从对比结果中不放回地随机抽取`n`个字段，保持原有的顺序，`n`不小于字段总数时返回全部字段
+///
+/// 使用相同种子的`rng`时抽取的字段相同
+pub fn sample_diff<R: Rng>(r: DiffResult, n: usize, rng: &mut R) -> DiffResult {
+    if n >= r.len() {
+        return r;
+    }
+    let mut picked = vec![false; r.len()];
+    for i in index::sample(rng, r.len(), n) {
+        picked[i] = true;
+    }
+    r.into_iter()
+        .zip(picked)
+        .filter_map(|(unit, picked)| picked.then_some(unit))
+        .collect()
+}
+
+/// 输出抽样得到的正确率以及置信区间的半宽，如`92.3% (±1.8%, n=2000)`
+///
+/// 置信区间为Wilson区间，区间不对称时取上下限之差的一半，`confidence`为置信水平，如0.95
+pub fn print_sampled_accuracy(report: &ScoringReport, confidence: f64) {
+    let (low, high) = wilson_interval(report.matched, report.total, confidence);
+    println!(
+        "sampled classification accuracy: {:.1}% (±{:.1}%, n={})",
+        report.overall * 100f64,
+        (high - low) / 2.0 * 100f64,
+        report.total
+    );
+}
+
+/// 将分类成绩或者分类树序列化为`json`或者`yaml`格式的文本，以换行结尾，`yaml`需要开启`yaml`特性
+pub fn to_format<T: Serialize>(value: &T, format: &str) -> anyhow::Result<String> {
+    match format {
+        "json" => Ok(serde_json::to_string_pretty(value)? + "\n"),
+        #[cfg(feature = "yaml")]
+        "yaml" => Ok(serde_yaml::to_string(value)?),
+        _ => Err(anyhow::Error::msg(format!(
+            "unsupported output format [{}]",
+            format
+        ))),
+    }
+}
+
+/// 每个对比结果输出为一行JSON（JSON Lines），边生成边输出
+pub fn write_json_lines<W: Write>(
+    units: impl Iterator<Item = DiffUnit>,
+    mut w: W,
+) -> anyhow::Result<()> {
+    for unit in units {
+        serde_json::to_writer(&mut w, &unit)?;
+        writeln!(w)?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// 读取保存的对比结果，用于不重新对比而直接重新汇总
+///
+/// 支持本程序输出的各种格式：
+/// - `.csv`：`--fields-csv`按`--report-fields`选择的列输出的CSV，以及`-o`输出的`path,field,matched`，
+///   后者的字段为`db-table-field`，按前两个`-`拆分
+/// - `.json`：`-o`输出的包含`units`的对象，或者对比结果的数组
+/// - `.jsonl`：`--json-lines`的输出，每行为完整的对比结果或者只包含`--report-fields`选择的列
+///
+/// 其他扩展名时，内容整体是JSON对象或者数组时按`.json`读取，否则按`.jsonl`读取。
+/// 必须有分类路径列（`path`）以及匹配结果列（`status`、`matched`或者`match_kind`），
+/// 分类路径为空的行报错
+pub fn load_diff_result(path: &Path) -> anyhow::Result<DiffResult> {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    let context = |row: usize, e: anyhow::Error| {
+        anyhow::Error::msg(format!(
+            "invalid diff unit at line {} of [{}]: {}",
+            row,
+            path.to_string_lossy(),
+            e
+        ))
+    };
+    if ext.as_deref() == Some("csv") {
+        let mut rdr = csv::Reader::from_path(path)?;
+        let headers = rdr.headers()?.clone();
+        return rdr
+            .records()
+            .enumerate()
+            .map(|(i, record)| {
+                let record = record?;
+                unit_from_columns(|name| {
+                    headers
+                        .iter()
+                        .position(|head| head == name)
+                        .and_then(|col| record.get(col))
+                        .map(String::from)
+                })
+                // 第1行为列名
+                .map_err(|e| context(i + 2, e))
+            })
+            .collect();
+    }
+
+    let content = fs::read_to_string(path)?;
+    let document = match ext.as_deref() {
+        Some("json") => Some(serde_json::from_str(&content)?),
+        Some("jsonl") => None,
+        _ => serde_json::from_str::<serde_json::Value>(&content)
+            .ok()
+            .filter(|doc| doc.is_array() || doc.is_object()),
+    };
+    if let Some(document) = document {
+        let units = match document {
+            serde_json::Value::Object(mut obj) => obj.remove("units").ok_or_else(|| {
+                anyhow::Error::msg(format!(
+                    "[{}] is a JSON object without units",
+                    path.to_string_lossy()
+                ))
+            })?,
+            doc => doc,
+        };
+        let serde_json::Value::Array(units) = units else {
+            return Err(anyhow::Error::msg(format!(
+                "the units of [{}] are not an array",
+                path.to_string_lossy()
+            )));
+        };
+        return units
+            .into_iter()
+            .enumerate()
+            .map(|(i, unit)| unit_from_json(unit).map_err(|e| context(i + 1, e)))
+            .collect();
+    }
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .map_err(anyhow::Error::from)
+                .and_then(unit_from_json)
+                .map_err(|e| context(i + 1, e))
+        })
+        .collect()
+}
+
+/// 从JSON读取一个对比结果，可以是完整的`DiffUnit`，也可以是`ReportColumns::json`输出的对象
+fn unit_from_json(value: serde_json::Value) -> anyhow::Result<DiffUnit> {
+    let serde_json::Value::Object(obj) = value else {
+        return Err(anyhow::Error::msg("expected a JSON object"));
+    };
+    let unit = if obj.contains_key("classis") {
+        serde_json::from_value::<DiffUnit>(serde_json::Value::Object(obj))?
+    } else {
+        unit_from_columns(|name| match obj.get(name)? {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Null => None,
+            other => Some(other.to_string()),
+        })?
+    };
+    if unit.classis.iter().all(|classi| classi.is_empty()) {
+        return Err(anyhow::Error::msg("the classification path is empty"));
+    }
+    Ok(unit)
+}
+
+/// 按列名读取一个对比结果，`get`返回该行中某一列的值，没有该列时返回`None`
+///
+/// 列名和`ReportField`相同，另外接受`-o`输出的`matched`列
+fn unit_from_columns(get: impl Fn(&str) -> Option<String>) -> anyhow::Result<DiffUnit> {
+    let get_any = |names: &[&str]| names.iter().find_map(|name| get(name));
+    let path = get_any(&["path", "expected_path", "classis"])
+        .ok_or_else(|| anyhow::Error::msg("no path column"))?;
+    if path.trim().is_empty() {
+        return Err(anyhow::Error::msg("the classification path is empty"));
+    }
+    let classis = path.split('/').map(String::from).collect();
+
+    let field = match get_any(&["db", "database"]) {
+        Some(db) => FieldMeta(
+            db,
+            get("table").unwrap_or_default(),
+            get("field").unwrap_or_default(),
+        ),
+        None => {
+            let field = get("field").ok_or_else(|| anyhow::Error::msg("no field column"))?;
+            let mut parts = field.splitn(3, '-').map(String::from);
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(db), Some(table), Some(field)) => FieldMeta(db, table, field),
+                _ => {
+                    return Err(anyhow::Error::msg(format!(
+                        "field [{}] is not in the form db-table-field",
+                        field
+                    )))
+                }
+            }
+        }
+    };
+
+    let match_kind = match (
+        get("match_kind"),
+        get("status"),
+        get_any(&["matched", "field_exist"]),
+    ) {
+        (Some(kind), _, _) => match kind.as_str() {
+            "Exact" => MatchKind::Exact,
+            "Partial" => MatchKind::Partial,
+            "Missing" => MatchKind::Missing,
+            _ => return Err(anyhow::Error::msg(format!("unknown match kind [{}]", kind))),
+        },
+        (None, Some(status), _) => match status.as_str() {
+            "matched" => MatchKind::Exact,
+            "partial" => MatchKind::Partial,
+            "missing" => MatchKind::Missing,
+            _ => return Err(anyhow::Error::msg(format!("unknown status [{}]", status))),
+        },
+        (None, None, Some(matched)) => {
+            if matched.parse::<bool>()? {
+                MatchKind::Exact
+            } else {
+                MatchKind::Missing
+            }
+        }
+        (None, None, None) => {
+            return Err(anyhow::Error::msg(
+                "no status, matched or match_kind column",
+            ))
+        }
+    };
+    let missing_level = match get("missing_level").filter(|level| !level.is_empty()) {
+        Some(level) => Some(level.parse::<usize>()?),
+        None => None,
+    };
+
+    Ok(DiffUnit {
+        classis,
+        field,
+        field_exist: match_kind == MatchKind::Exact,
+        match_kind,
+        missing_level,
+    })
+}
+
+/// 输出分组汇总后的分类成绩，`top_n`限制输出的分组数量，`format`为`text`或者`json`
+pub fn print_grouped_report(
+    report: &ScoringReport,
+    top_n: Option<usize>,
+    format: &str,
+) -> anyhow::Result<()> {
+    let shown = top_n
+        .unwrap_or(report.per_class.len())
+        .min(report.per_class.len());
+    let groups = &report.per_class[..shown];
+    if format == "json" {
+        let report = ScoringReport {
+            per_class: groups.to_vec(),
+            ..*report
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!(
+        "total classification accuracy: {:.2}% ({}/{})",
+        report.overall * 100f64,
+        report.matched,
+        report.total
+    );
+    for g in groups {
+        println!(
+            "group [{}] accuracy: {:.2}% ({}/{})",
+            g.class,
+            g.accuracy * 100f64,
+            g.matched,
+            g.total
+        );
+    }
+    Ok(())
+}
+
+/// 逐字段导出对比结果时可以选择的列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportField {
+    Database,
+    Table,
+    Field,
+    /// 标准答案中的分类路径
+    Path,
+    /// 分类结果中的分类路径，分类结果中没有该字段时为空
+    ActualPath,
+    /// `matched`或者`missing`
+    Status,
+    MatchKind,
+    MissingLevel,
+}
+
+impl ReportField {
+    /// 列名以及可以使用的别名
+    const NAMES: [(&'static str, ReportField); 10] = [
+        ("db", ReportField::Database),
+        ("database", ReportField::Database),
+        ("table", ReportField::Table),
+        ("field", ReportField::Field),
+        ("path", ReportField::Path),
+        ("expected_path", ReportField::Path),
+        ("actual_path", ReportField::ActualPath),
+        ("status", ReportField::Status),
+        ("match_kind", ReportField::MatchKind),
+        ("missing_level", ReportField::MissingLevel),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReportField::Database => "db",
+            ReportField::Table => "table",
+            ReportField::Field => "field",
+            ReportField::Path => "path",
+            ReportField::ActualPath => "actual_path",
+            ReportField::Status => "status",
+            ReportField::MatchKind => "match_kind",
+            ReportField::MissingLevel => "missing_level",
+        }
+    }
+}
+
+impl FromStr for ReportField {
+    type Err = SisyphusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ReportField::NAMES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, field)| *field)
+            .ok_or_else(|| {
+                SisyphusError::InvalidOption(format!(
+                    "unknown report field [{}], expected one of {}",
+                    s,
+                    ReportField::NAMES
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            })
+    }
+}
+
+/// 逐字段导出对比结果时选择的列及其顺序
+pub struct ReportColumns {
+    fields: Vec<ReportField>,
+    /// 分类结果中各字段的分类路径，只在选择了`actual_path`时生成
+    actual_paths: HashMap<FieldMeta, String>,
+}
+
+impl ReportColumns {
+    /// 默认的列
+    pub const DEFAULT: &'static str = "db,table,field,path,status";
+
+    /// 解析逗号分隔的列名，如`field,status`，`answer`用于查找`actual_path`
+    pub fn parse(list: &str, answer: &ClassiTree) -> anyhow::Result<Self> {
+        let fields = list
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<ReportField>, _>>()?;
+        if fields.is_empty() {
+            return Err(anyhow::Error::msg("no report field selected"));
+        }
+        let mut actual_paths = HashMap::new();
+        if fields.contains(&ReportField::ActualPath) {
+            for (classis, field) in answer.field_paths() {
+                actual_paths
+                    .entry(field.clone())
+                    .or_insert_with(|| classis.join("/"));
+            }
+        }
+        Ok(Self {
+            fields,
+            actual_paths,
+        })
+    }
+
+    pub fn header(&self) -> Vec<&'static str> {
+        self.fields.iter().map(ReportField::name).collect()
+    }
+
+    fn text(&self, field: ReportField, unit: &DiffUnit) -> String {
+        match field {
+            ReportField::Database => unit.field.0.clone(),
+            ReportField::Table => unit.field.1.clone(),
+            ReportField::Field => unit.field.2.clone(),
+            ReportField::Path => unit.classis.join("/"),
+            ReportField::ActualPath => self
+                .actual_paths
+                .get(&unit.field)
+                .cloned()
+                .unwrap_or_default(),
+            ReportField::Status => String::from(if unit.field_exist {
+                "matched"
+            } else if unit.match_kind == MatchKind::Partial {
+                "partial"
+            } else {
+                "missing"
+            }),
+            ReportField::MatchKind => format!("{:?}", unit.match_kind),
+            ReportField::MissingLevel => unit
+                .missing_level
+                .map(|level| level.to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// 一个对比结果对应的一行
+    pub fn row(&self, unit: &DiffUnit) -> Vec<String> {
+        self.fields.iter().map(|f| self.text(*f, unit)).collect()
+    }
+
+    /// 一个对比结果对应的JSON对象，`missing_level`为数字或者`null`，其他列为字符串
+    pub fn json(&self, unit: &DiffUnit) -> serde_json::Value {
+        let obj = self
+            .fields
+            .iter()
+            .map(|f| {
+                let val = match f {
+                    ReportField::MissingLevel => serde_json::json!(unit.missing_level),
+                    _ => serde_json::Value::String(self.text(*f, unit)),
+                };
+                (f.name().to_string(), val)
+            })
+            .collect();
+        serde_json::Value::Object(obj)
+    }
+
+    /// 输出CSV，第一行为列名
+    pub fn write_csv<'a, W: Write>(
+        &self,
+        units: impl Iterator<Item = &'a DiffUnit>,
+        w: W,
+    ) -> anyhow::Result<()> {
+        let mut wtr = csv::Writer::from_writer(w);
+        wtr.write_record(self.header())?;
+        for unit in units {
+            wtr.write_record(self.row(unit))?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// 每个对比结果输出为一行JSON，只包含选择的列
+    pub fn write_json_lines<W: Write>(
+        &self,
+        units: impl Iterator<Item = DiffUnit>,
+        mut w: W,
+    ) -> anyhow::Result<()> {
+        for unit in units {
+            serde_json::to_writer(&mut w, &self.json(&unit))?;
+            writeln!(w)?;
+        }
+        w.flush()?;
+        Ok(())
+    }
+}
+
+/// 使用用户提供的模版渲染分类成绩
+///
+/// 模版语法见`tinytemplate`，可以使用`total`、`matched`、`overall`、`micro_accuracy`、`macro_accuracy`以及`per_class`列表
+pub fn render_report_template(template: &str, report: &ScoringReport) -> anyhow::Result<String> {
+    let mut tt = TinyTemplate::new();
+    tt.add_template("report", template)?;
+    Ok(tt.render("report", report)?)
+}
+
+/// 逐个说明未匹配字段的原因，分类级别使用标准答案的表头名称
+pub fn explain_misses(solution: &ClassiTree, r: &DiffResult) {
+    for unit in r.iter().filter(|unit| !unit.field_exist) {
+        match unit.missing_level {
+            Some(depth) => println!(
+                "field [{}]: {} '{}' not found",
+                unit.field,
+                solution.level_name(depth),
+                unit.classis.get(depth).map(String::as_str).unwrap_or("")
+            ),
+            None => println!(
+                "field [{}]: not found under {}",
+                unit.field,
+                unit.classis.join("/")
+            ),
+        }
+    }
+}
+
+/// 按表汇总未匹配的字段，输出每张表未匹配的字段数量和字段总数，未匹配字段最多的表在前
+pub fn rollup_misses_by_table(r: &DiffResult) {
+    let mut table_statistic = HashMap::<(&str, &str), (i32, i32)>::new();
+    for unit in r {
+        let e = table_statistic
+            .entry((unit.field.0.as_str(), unit.field.1.as_str()))
+            .or_insert((0, 0));
+        e.1 += 1;
+        if !unit.field_exist {
+            e.0 += 1;
+        }
+    }
+
+    let mut tables: Vec<_> = table_statistic
+        .into_iter()
+        .filter(|(_, (missing, _))| *missing > 0)
+        .collect();
+    tables.sort_by(|(a, (a_missing, _)), (b, (b_missing, _))| {
+        b_missing.cmp(a_missing).then_with(|| a.cmp(b))
+    });
+
+    for ((db, tb), (missing, total)) in tables {
+        println!("table {}-{}: {}/{} fields missing", db, tb, missing, total);
+    }
+}
+
+/// 将未匹配的字段中，在分类结果中的分类路径是标准答案分类路径真前缀的标记为`MatchKind::Partial`，
+/// 例如标准答案为`A/B/C`、分类结果为`A/B`，返回标记的数量
+///
+/// 部分匹配仍不算匹配，只是和分类错误区分开；字段在分类结果中有多个分类路径时使用最先找到的路径
+pub fn mark_partial_paths(answer: &ClassiTree, r: &mut DiffResult) -> usize {
+    let mut marked = 0;
+    for unit in r.iter_mut().filter(|unit| !unit.field_exist) {
+        let is_prefix = answer
+            .field_path(&unit.field)
+            .is_some_and(|path| path.len() < unit.classis.len() && unit.classis.starts_with(path));
+        if is_prefix {
+            unit.match_kind = MatchKind::Partial;
+            marked += 1;
+        }
+    }
+    marked
+}
+
+/// 检查未匹配的字段是否因为分类结果把表名和字段名两列填反
+///
+/// 返回交换表名和字段名后能在分类结果中找到的标准答案字段
+pub fn detect_swaps(answer: &ClassiTree, r: &DiffResult) -> Vec<FieldMeta> {
+    let answer_fields: HashSet<&FieldMeta> = answer.fields().collect();
+
+    r.iter()
+        .filter(|unit| !unit.field_exist)
+        .map(|unit| &unit.field)
+        .filter(|field| {
+            field.1 != field.2
+                && answer_fields.contains(&FieldMeta(
+                    field.0.clone(),
+                    field.2.clone(),
+                    field.1.clone(),
+                ))
+        })
+        .cloned()
+        .collect()
+}
+
+/// 同一份分类结果两个版本之间的变动，不涉及标准答案
+#[derive(Serialize, Debug, Default)]
+pub struct ChurnStats {
+    /// 新版本中新增的字段数量
+    pub added: usize,
+    /// 新版本中删除的字段数量
+    pub removed: usize,
+    /// 两个版本中都有但分类路径不同的字段数量
+    pub moved: usize,
+    /// 两个版本中分类路径相同的字段数量
+    pub unchanged: usize,
+}
+
+impl ChurnStats {
+    /// 发生变动的字段数量
+    pub fn changed(&self) -> usize {
+        self.added + self.removed + self.moved
+    }
+
+    pub fn print(&self) {
+        println!("added: {}", self.added);
+        println!("removed: {}", self.removed);
+        println!("moved: {}", self.moved);
+        println!("unchanged: {}", self.unchanged);
+        println!("changed: {}", self.changed());
+    }
+}
+
+/// 对比同一份分类结果的两个版本`a`（旧）和`b`（新），统计新增、删除以及分类路径变化的字段
+pub fn answer_churn(a: &ClassiTree, b: &ClassiTree) -> ChurnStats {
+    let reconciliation = a.reconcile(b);
+    let mut res = ChurnStats {
+        added: reconciliation.extra.len(),
+        removed: reconciliation.missing.len(),
+        ..Default::default()
+    };
+    for field in a.field_set() {
+        match (a.field_path(&field), b.field_path(&field)) {
+            (Some(old), Some(new)) if old == new => res.unchanged += 1,
+            (Some(_), Some(_)) => res.moved += 1,
+            _ => (),
+        }
+    }
+    res
+}
+
+/// 字段对账结果
+pub struct Reconciliation {
+    /// 标准答案中有而分类结果中没有的字段
+    pub missing: Vec<FieldMeta>,
+    /// 分类结果中有而标准答案中没有的字段
+    pub extra: Vec<FieldMeta>,
+}
+
+impl Reconciliation {
+    /// 将两个列表左右对齐输出
+    pub fn print(&self) {
+        let left_title = format!("in solution but not answer ({})", self.missing.len());
+        let right_title = format!("in answer but not solution ({})", self.extra.len());
+        let left: Vec<String> = self.missing.iter().map(|f| f.to_string()).collect();
+        let right: Vec<String> = self.extra.iter().map(|f| f.to_string()).collect();
+        // 按显示宽度对齐，中文字段名占两列
+        let width = left
+            .iter()
+            .map(|l| display_width(l))
+            .chain([display_width(&left_title)])
+            .max()
+            .unwrap_or(0);
+
+        println!("{} | {}", fit_to_width(&left_title, width), right_title);
+        for i in 0..left.len().max(right.len()) {
+            println!(
+                "{} | {}",
+                fit_to_width(left.get(i).map(String::as_str).unwrap_or(""), width),
+                right.get(i).map(String::as_str).unwrap_or(""),
+            );
+        }
+    }
+
+    /// 输出CSV，每行一个字段，`side`标明字段只出现在哪一侧
+    pub fn write_csv(&self, path: &PathBuf) -> anyhow::Result<()> {
+        let mut wtr = csv::Writer::from_path(path)?;
+        wtr.write_record(["side", "database", "table", "field"])?;
+        for (side, fields) in [
+            ("solution_only", &self.missing),
+            ("answer_only", &self.extra),
+        ] {
+            for field in fields {
+                wtr.write_record([side, field.0.as_str(), field.1.as_str(), field.2.as_str()])?;
+            }
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+/// 分类体系的结构差异，路径以`/`连接各级分类名称
+pub struct TaxonomyDiff {
+    /// 分类结果中有而标准答案中没有的分类路径
+    pub added: Vec<String>,
+    /// 标准答案中有而分类结果中没有的分类路径
+    pub removed: Vec<String>,
+}
+
+impl TaxonomyDiff {
+    /// 依次输出两个列表
+    pub fn print(&self) {
+        println!(
+            "class paths in solution but not answer ({})",
+            self.removed.len()
+        );
+        for path in &self.removed {
+            println!("  {}", path);
+        }
+        println!(
+            "class paths in answer but not solution ({})",
+            self.added.len()
+        );
+        for path in &self.added {
+            println!("  {}", path);
+        }
+    }
+
+    /// 以统一差异格式输出，删除的分类路径以`- `开头，新增的以`+ `开头，按路径排列
+    pub fn to_patch(&self) -> String {
+        let mut lines: Vec<(&str, char)> = self
+            .removed
+            .iter()
+            .map(|path| (path.as_str(), '-'))
+            .chain(self.added.iter().map(|path| (path.as_str(), '+')))
+            .collect();
+        lines.sort();
+        let mut res = String::from("--- solution\n+++ answer\n");
+        for (path, sign) in lines {
+            res.push_str(&format!("{} {}\n", sign, path));
+        }
+        res
+    }
+}
+
+/// 出现在多个不同上级分类下的同名分类
+pub struct HierarchyConflict {
+    pub class: String,
+    /// 各个上级分类的路径，顶级分类的上级路径为空
+    pub parents: Vec<String>,
+}
+
+impl Display for HierarchyConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parents: Vec<&str> = self
+            .parents
+            .iter()
+            .map(|p| if p.is_empty() { "<root>" } else { p.as_str() })
+            .collect();
+        write!(
+            f,
+            "class '{}' appears under {} parents: {}",
+            self.class,
+            parents.len(),
+            parents.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sisyphus-report-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn tree(rows: &[([&str; 2], &str)]) -> ClassiTree {
+        let mut tree = ClassiTree::new();
+        for (classis, field) in rows {
+            tree.add_node(
+                classis,
+                FieldMeta("crm".into(), "user".into(), field.to_string()),
+            )
+            .unwrap();
+        }
+        tree
+    }
+
+    /// 标准答案和分类结果，`phone`分错了大类，`email`没有分类
+    fn sample() -> (ClassiTree, DiffResult) {
+        let solution = tree(&[
+            (["个人信息", "身份信息"], "name"),
+            (["个人信息", "联系方式"], "phone"),
+            (["个人信息", "联系方式"], "email"),
+            (["交易信息", "订单"], "order_no"),
+        ]);
+        let answer = tree(&[
+            (["个人信息", "身份信息"], "name"),
+            (["交易信息", "订单"], "phone"),
+            (["交易信息", "订单"], "order_no"),
+        ]);
+        let diff = solution.diff(&answer);
+        (answer, diff)
+    }
+
+    fn units(r: &DiffResult) -> Vec<(String, FieldMeta, bool, MatchKind)> {
+        let mut units: Vec<_> = r
+            .iter()
+            .map(|unit| {
+                (
+                    unit.classis.join("/"),
+                    unit.field.clone(),
+                    unit.field_exist,
+                    unit.match_kind,
+                )
+            })
+            .collect();
+        units.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        units
+    }
+
+    #[test]
+    fn load_diff_result_reads_the_written_formats() {
+        let dir = temp_dir("load");
+        let (answer, diff) = sample();
+        let report = ScoringReport::from(&diff);
+        let expected = units(&diff);
+        assert_eq!(expected.iter().filter(|unit| unit.2).count(), 2);
+
+        for (name, format) in [("output.json", "json"), ("output.csv", "csv")] {
+            let file = dir.join(name);
+            report.write_output(&diff, &file, format).unwrap();
+            assert_eq!(
+                units(&load_diff_result(&file).unwrap()),
+                expected,
+                "{}",
+                name
+            );
+        }
+
+        for list in [
+            ReportColumns::DEFAULT,
+            "path,db,table,field,match_kind",
+            "db,table,field,path,status,missing_level,actual_path",
+        ] {
+            let columns = ReportColumns::parse(list, &answer).unwrap();
+            let csv_file = dir.join("fields.csv");
+            columns
+                .write_csv(diff.iter(), fs::File::create(&csv_file).unwrap())
+                .unwrap();
+            assert_eq!(
+                units(&load_diff_result(&csv_file).unwrap()),
+                expected,
+                "{}",
+                list
+            );
+
+            let jsonl_file = dir.join("fields.jsonl");
+            let mut buf = Vec::new();
+            columns
+                .write_json_lines(diff.iter().map(clone_unit), &mut buf)
+                .unwrap();
+            fs::write(&jsonl_file, buf).unwrap();
+            assert_eq!(
+                units(&load_diff_result(&jsonl_file).unwrap()),
+                expected,
+                "{}",
+                list
+            );
+        }
+
+        let mut buf = Vec::new();
+        write_json_lines(diff.iter().map(clone_unit), &mut buf).unwrap();
+        let jsonl_file = dir.join("units");
+        fs::write(&jsonl_file, buf).unwrap();
+        assert_eq!(units(&load_diff_result(&jsonl_file).unwrap()), expected);
+
+        let array_file = dir.join("units.json");
+        fs::write(&array_file, serde_json::to_string(&diff).unwrap()).unwrap();
+        assert_eq!(units(&load_diff_result(&array_file).unwrap()), expected);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn clone_unit(unit: &DiffUnit) -> DiffUnit {
+        DiffUnit {
+            classis: unit.classis.clone(),
+            field: unit.field.clone(),
+            field_exist: unit.field_exist,
+            match_kind: unit.match_kind,
+            missing_level: unit.missing_level,
+        }
+    }
+
+    #[test]
+    fn load_diff_result_rejects_rows_without_path() {
+        let dir = temp_dir("empty-path");
+        let csv_file = dir.join("fields.csv");
+        fs::write(
+            &csv_file,
+            "db,table,field,path,status\ncrm,user,name,个人信息,matched\ncrm,user,phone,,missing\n",
+        )
+        .unwrap();
+        let e = load_diff_result(&csv_file).unwrap_err();
+        assert!(e.to_string().contains("line 3"), "{}", e);
+
+        let jsonl_file = dir.join("units.jsonl");
+        fs::write(
+            &jsonl_file,
+            r#"{"classis":[],"field":["crm","user","name"],"field_exist":true}"#,
+        )
+        .unwrap();
+        assert!(load_diff_result(&jsonl_file).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_diff_scores_zero() {
+        let report = ScoringReport::from(&DiffResult::new());
+        assert_eq!((report.total, report.matched), (0, 0));
+        assert_eq!(report.overall, 0.0);
+        let grouped = ScoringReport::grouped(&DiffResult::new(), GroupBy::Class(1));
+        assert_eq!(grouped.overall, 0.0);
+
+        let unit = DiffUnit {
+            classis: Vec::new(),
+            field: FieldMeta("crm".into(), "user".into(), "name".into()),
+            field_exist: true,
+            match_kind: MatchKind::Exact,
+            missing_level: None,
+        };
+        let report = ScoringReport::from(&vec![unit]);
+        assert_eq!(report.per_class[0].class, "");
+        assert_eq!(report.overall, 1.0);
+    }
+
+    #[test]
+    fn to_format_json_shape_and_round_trip() {
+        let (_, diff) = sample();
+        let report = ScoringReport::from(&diff);
+        let json = to_format(&report, "json").unwrap();
+        assert!(json.ends_with('\n'));
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["total"], 4);
+        assert_eq!(value["matched"], 2);
+        assert_eq!(value["overall"], 0.5);
+        assert_eq!(value["average"], "micro");
+        assert_eq!(
+            value["per_class"],
+            serde_json::json!([
+                {"class": "个人信息", "total": 3, "matched": 1, "accuracy": 1.0 / 3.0},
+                {"class": "交易信息", "total": 1, "matched": 1, "accuracy": 1.0},
+            ])
+        );
+        let back: ScoringReport = serde_json::from_str(&json).unwrap();
+        assert_eq!((back.total, back.matched, back.overall), (4, 2, 0.5));
+        assert_eq!(back.per_class.len(), 2);
+
+        let mut solution = tree(&[
+            (["个人信息", "身份信息"], "name"),
+            (["个人信息", "身份信息"], "id_card"),
+        ]);
+        solution.level_names = vec![String::from("一级分类"), String::from("二级分类")];
+        let json = to_format(&solution, "json").unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!({
+                "level_names": ["一级分类", "二级分类"],
+                "classes": [{
+                    "name": "个人信息",
+                    "classes": [{
+                        "name": "身份信息",
+                        "fields": [["crm", "user", "name"], ["crm", "user", "id_card"]],
+                    }],
+                }],
+            })
+        );
+        let back: ClassiTree = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.to_string(), solution.to_string());
+        assert_eq!(back.level_name(1), "二级分类");
+
+        assert!(to_format(&report, "toml").is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn to_format_yaml_round_trip() {
+        let (_, diff) = sample();
+        let report = ScoringReport::from(&diff);
+        let yaml = to_format(&report, "yaml").unwrap();
+        let back: ScoringReport = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!((back.total, back.matched, back.overall), (4, 2, 0.5));
+        assert_eq!(back.per_class[0].class, "个人信息");
+
+        let solution = tree(&[(["个人信息", "身份信息"], "name")]);
+        let yaml = to_format(&solution, "yaml").unwrap();
+        assert!(yaml.contains("name: 个人信息"), "{}", yaml);
+        let back: ClassiTree = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(back.to_string(), solution.to_string());
+    }
+
+    #[test]
+    fn class_lines_keep_the_plain_format_without_width() {
+        let (_, diff) = sample();
+        let mut report = ScoringReport::from(&diff);
+        report.include_empty_classes(vec![String::from("企业信息")]);
+        let classes: Vec<&str> = report.per_class.iter().map(|c| c.class.as_str()).collect();
+        let lines = class_lines(&report, None, None);
+        let expected: Vec<String> = classes
+            .iter()
+            .map(|class| match *class {
+                "个人信息" => String::from("classification [个人信息] accuracy: 33.33%"),
+                "交易信息" => String::from("classification [交易信息] accuracy: 100.00%"),
+                _ => String::from("classification [企业信息] has no fields (0/0)"),
+            })
+            .collect();
+        assert_eq!(lines, expected);
+
+        let marked = class_lines(&report, None, Some(0.5));
+        assert!(marked
+            .iter()
+            .any(|line| line == "! classification [个人信息] accuracy: 33.33%"));
+
+        for line in class_lines(&report, Some(60), None) {
+            assert!(display_width(&line) <= 60, "{}", line);
+            assert!(line.contains('#') || line.contains("no fields"), "{}", line);
+        }
+    }
+}
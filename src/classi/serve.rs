@@ -0,0 +1,251 @@
+//! 为上传的分类结果打分的HTTP服务
+//!
+//! `POST /score`上传分类结果文件，可以是`multipart/form-data`中的第一个文件，也可以直接作为请求体，
+//! 返回JSON格式的`ScoringReport`；上传的文件只在内存中处理，不会写入磁盘
+
+use std::io::Read;
+
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use super::{
+    reader::{read_classi_result_from_bytes, InputFormat, ParseConfig},
+    report::score,
+    tree::ClassiTree,
+};
+
+/// 上传文件的大小上限
+const MAX_UPLOAD: u64 = 64 * 1024 * 1024;
+
+/// 在`bind`地址上启动服务，标准答案在启动时读取一次，之后每个请求都和它对比
+pub fn run(bind: &str, solution: &ClassiTree, config: &ParseConfig) -> anyhow::Result<()> {
+    let server = Server::http(bind).map_err(|e| anyhow::Error::msg(e.to_string()))?;
+    eprintln!("listening on {}", bind);
+    serve(server.incoming_requests(), solution, config);
+    Ok(())
+}
+
+/// 依次处理请求，`POST /score`返回成绩，出错时返回400，其他路径返回404
+fn serve(requests: impl Iterator<Item = Request>, solution: &ClassiTree, config: &ParseConfig) {
+    for mut request in requests {
+        let is_score = *request.method() == Method::Post && request.url() == "/score";
+        let (status, body) = if is_score {
+            match handle_score(&mut request, solution, config) {
+                Ok(body) => (200, body),
+                Err(e) => (
+                    400,
+                    serde_json::json!({ "error": format!("{:#}", e) }).to_string(),
+                ),
+            }
+        } else {
+            (404, serde_json::json!({ "error": "not found" }).to_string())
+        };
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(
+                Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap(),
+            );
+        if let Err(e) = request.respond(response) {
+            eprintln!("warning: failed to respond: {}", e);
+        }
+    }
+}
+
+/// 读取上传的分类结果并打分，返回JSON格式的成绩
+fn handle_score(
+    request: &mut Request,
+    solution: &ClassiTree,
+    config: &ParseConfig,
+) -> anyhow::Result<String> {
+    let content_type = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Content-Type"))
+        .map(|h| h.value.as_str().to_string())
+        .unwrap_or_default();
+
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_UPLOAD + 1)
+        .read_to_end(&mut body)?;
+    if body.len() as u64 > MAX_UPLOAD {
+        return Err(anyhow::Error::msg(format!(
+            "the upload exceeds {} bytes",
+            MAX_UPLOAD
+        )));
+    }
+
+    let (bytes, input_format) = match boundary(&content_type) {
+        Some(boundary) => {
+            let (filename, content) = multipart_file(&body, &boundary)
+                .ok_or_else(|| anyhow::Error::msg("no file found in the multipart request"))?;
+            (content.to_vec(), format_of(&filename))
+        }
+        None if content_type.starts_with("text/csv") => (body, InputFormat::Csv),
+        None => (body, InputFormat::Xlsx),
+    };
+    let answer = read_classi_result_from_bytes(&bytes, input_format, config)?;
+    Ok(serde_json::to_string(&score(solution, &answer))?)
+}
+
+/// 按上传文件名的扩展名确定格式，`.csv`为CSV，其他为xlsx
+fn format_of(filename: &str) -> InputFormat {
+    if filename.to_lowercase().ends_with(".csv") {
+        InputFormat::Csv
+    } else {
+        InputFormat::Xlsx
+    }
+}
+
+/// `multipart/form-data; boundary=...`中的分隔符，不是multipart时返回`None`
+fn boundary(content_type: &str) -> Option<String> {
+    if !content_type.starts_with("multipart/form-data") {
+        return None;
+    }
+    content_type
+        .split(';')
+        .filter_map(|param| param.trim().strip_prefix("boundary="))
+        .map(|b| b.trim_matches('"').to_string())
+        .next()
+}
+
+/// 取出multipart请求体中第一个带有`filename`的部分，返回文件名和文件内容
+fn multipart_file<'a>(body: &'a [u8], boundary: &str) -> Option<(String, &'a [u8])> {
+    let delimiter = format!("--{}", boundary);
+    let mut rest = &body[find(body, delimiter.as_bytes())? + delimiter.len()..];
+    // 最后一个分隔符之后是`--`
+    while !rest.starts_with(b"--") {
+        let end = find(rest, delimiter.as_bytes())?;
+        let part = rest[..end].strip_prefix(b"\r\n")?;
+        rest = &rest[end + delimiter.len()..];
+
+        let header_end = find(part, b"\r\n\r\n")?;
+        let headers = String::from_utf8_lossy(&part[..header_end]);
+        let Some(filename) = headers
+            .split(';')
+            .filter_map(|param| param.trim().strip_prefix("filename="))
+            .map(|name| name.lines().next().unwrap_or_default().trim_matches('"'))
+            .next()
+        else {
+            continue;
+        };
+        let content = &part[header_end + 4..];
+        let content = content.strip_suffix(b"\r\n").unwrap_or(content);
+        return Some((filename.to_string(), content));
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        net::{SocketAddr, TcpStream},
+        thread,
+    };
+
+    use super::*;
+
+    const ANSWER: &str = "一级分类,数据库名称,表名,字段名\n\
+                          个人信息,crm,user,name\n\
+                          交易信息,crm,user,phone\n";
+
+    fn solution() -> ClassiTree {
+        let csv = "一级分类,数据库名称,表名,字段名\n\
+                   个人信息,crm,user,name\n\
+                   个人信息,crm,user,phone\n";
+        read_classi_result_from_bytes(
+            &csv.as_bytes().to_vec(),
+            InputFormat::Csv,
+            &ParseConfig::default(),
+        )
+        .unwrap()
+    }
+
+    /// 发送一个HTTP请求，返回状态码和响应体
+    fn request(addr: SocketAddr, head: &str, body: &[u8]) -> (u16, serde_json::Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{}\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            head,
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = &response[response.find("\r\n\r\n").unwrap() + 4..];
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn score_requests() {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        let client = thread::spawn(move || {
+            let csv = request(
+                addr,
+                "POST /score HTTP/1.1\r\nContent-Type: text/csv",
+                ANSWER.as_bytes(),
+            );
+            let multipart_body = format!(
+                "--XYZ\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nhello\r\n\
+                 --XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"answer.csv\"\r\n\
+                 Content-Type: text/csv\r\n\r\n{}\r\n--XYZ--\r\n",
+                ANSWER
+            );
+            let multipart = request(
+                addr,
+                "POST /score HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=XYZ",
+                multipart_body.as_bytes(),
+            );
+            let invalid = request(addr, "POST /score HTTP/1.1", b"not a workbook");
+            let not_found = request(addr, "GET /score HTTP/1.1", b"");
+            (csv, multipart, invalid, not_found)
+        });
+
+        serve(
+            server.incoming_requests().take(4),
+            &solution(),
+            &ParseConfig::default(),
+        );
+        let (csv, multipart, invalid, not_found) = client.join().unwrap();
+
+        for (status, report) in [csv, multipart] {
+            assert_eq!(status, 200);
+            assert_eq!(report["total"], 2);
+            assert_eq!(report["matched"], 1);
+        }
+        assert_eq!(invalid.0, 400);
+        assert!(invalid.1["error"].as_str().is_some());
+        assert_eq!(
+            not_found,
+            (404, serde_json::json!({ "error": "not found" }))
+        );
+    }
+
+    #[test]
+    fn multipart_file_skips_parts_without_filename() {
+        let body = b"--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n\
+                     --b\r\nContent-Disposition: form-data; name=\"f\"; filename=\"x.CSV\"\r\n\r\nA,B\r\n--b--\r\n";
+        let (filename, content) = multipart_file(body, "b").unwrap();
+        assert_eq!(filename, "x.CSV");
+        assert_eq!(content, b"A,B");
+        assert!(matches!(format_of(&filename), InputFormat::Csv));
+
+        assert_eq!(
+            boundary("multipart/form-data; boundary=\"b\"").as_deref(),
+            Some("b")
+        );
+        assert_eq!(boundary("text/csv"), None);
+        assert!(multipart_file(b"--b\r\nname\r\n\r\nx\r\n--b--", "b").is_none());
+    }
+}
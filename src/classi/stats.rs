@@ -0,0 +1,43 @@
+//! 成绩统计用到的统计方法，如置信区间和百分位数
+
+/// 标准正态分布的分位数，即`P(Z <= z) = p`时的`z`
+///
+/// 使用Abramowitz & Stegun 26.2.23的有理逼近，误差小于4.5e-4，`p`须在0到1之间
+pub fn normal_quantile(p: f64) -> f64 {
+    const C: [f64; 3] = [2.515517, 0.802853, 0.010328];
+    const D: [f64; 3] = [1.432788, 0.189269, 0.001308];
+    let tail = |q: f64| {
+        let t = (-2.0 * q.ln()).sqrt();
+        t - (C[0] + C[1] * t + C[2] * t * t) / (1.0 + D[0] * t + D[1] * t * t + D[2] * t * t * t)
+    };
+    if p < 0.5 {
+        -tail(p)
+    } else {
+        tail(1.0 - p)
+    }
+}
+
+/// 从大到小排列的数值中，至少`q`比例的数值不小于的那个值，如`q`为0.9时为90%的数值都能达到的值
+///
+/// `sorted_desc`为空时返回`None`，`q`须在0到1之间
+pub fn percentile_desc(sorted_desc: &[usize], q: f64) -> Option<usize> {
+    if sorted_desc.is_empty() {
+        return None;
+    }
+    let rank = (q * sorted_desc.len() as f64).ceil() as usize;
+    Some(sorted_desc[rank.clamp(1, sorted_desc.len()) - 1])
+}
+
+/// 正确率的Wilson置信区间，返回区间的下限和上限，`confidence`为置信水平，如0.95
+pub fn wilson_interval(matched: i32, total: i32, confidence: f64) -> (f64, f64) {
+    if total <= 0 {
+        return (0.0, 1.0);
+    }
+    let n = total as f64;
+    let p = matched as f64 / n;
+    let z = normal_quantile(1.0 - (1.0 - confidence) / 2.0);
+    let z2 = z * z;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let half = z / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    ((center - half).max(0.0), (center + half).min(1.0))
+}
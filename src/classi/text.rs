@@ -0,0 +1,46 @@
+//! 终端输出的文本排版，按显示宽度而不是字节数或者字符数截断和补齐，中文等宽字符占两列
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// 没有指定宽度并且无法获取终端宽度时使用的宽度
+const DEFAULT_WIDTH: usize = 80;
+
+/// 当前终端的宽度，不是终端（如重定向到文件）时为80
+pub fn terminal_width() -> usize {
+    terminal_size::terminal_size().map_or(DEFAULT_WIDTH, |(terminal_size::Width(w), _)| w as usize)
+}
+
+/// 字符串在终端中的显示宽度
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// 截断到不超过`width`列，被截断时以`…`结尾
+pub fn truncate_to_width(s: &str, width: usize) -> String {
+    if display_width(s) <= width {
+        return s.to_string();
+    }
+    let mut res = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = UnicodeWidthChar::width(c).unwrap_or(0);
+        // 留一列给省略号
+        if used + w + 1 > width {
+            break;
+        }
+        res.push(c);
+        used += w;
+    }
+    if width > 0 {
+        res.push('…');
+    }
+    res
+}
+
+/// 截断或者在右侧补空格，使显示宽度恰好为`width`列
+pub fn fit_to_width(s: &str, width: usize) -> String {
+    let mut res = truncate_to_width(s, width);
+    let w = display_width(&res);
+    res.push_str(&" ".repeat(width.saturating_sub(w)));
+    res
+}
@@ -0,0 +1,1245 @@
+//! 分类树，以及两棵分类树之间的对比
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use serde::{ser::SerializeTupleStruct, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::report::{HierarchyConflict, Reconciliation, TaxonomyDiff};
+use crate::error::SisyphusError;
+use crate::resource::{Resource, ResourceType};
+
+/// 分类错误的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassiErrorKind {
+    /// 要添加的节点已经存在
+    NodeExists,
+    /// 没有提供分类级别
+    LevelsMissing,
+    /// 无法读取表头
+    HeaderNotFound,
+    /// 存在重复的字段
+    DuplicatedField,
+    /// 分类级别数量超过上限
+    TooManyLevels,
+    /// 分类级别数量低于下限
+    TooFewLevels,
+    /// 数据库名、表名或者字段名为空
+    EmptyIdentifier,
+    /// 分类级别的表头和模版不一致
+    TemplateMismatch,
+    /// 分类或者数据库名、表名、字段名中有不可见字符
+    InvisibleCharacter,
+    /// 表头和要求的表头不完全相同
+    HeaderMismatch,
+}
+
+impl ClassiErrorKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ClassiErrorKind::NodeExists => "the node exists",
+            ClassiErrorKind::LevelsMissing => "classification levels must be provided",
+            ClassiErrorKind::HeaderNotFound => "failed to retrieve the header",
+            ClassiErrorKind::DuplicatedField => "duplicated field detected",
+            ClassiErrorKind::TooManyLevels => "too many classification levels",
+            ClassiErrorKind::TooFewLevels => "too few classification levels",
+            ClassiErrorKind::EmptyIdentifier => "empty database, table or field name",
+            ClassiErrorKind::TemplateMismatch => "classification headers do not match the template",
+            ClassiErrorKind::InvisibleCharacter => "invisible characters detected",
+            ClassiErrorKind::HeaderMismatch => "the header does not match the required headers",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ClassiError {
+    kind: ClassiErrorKind,
+}
+
+impl ClassiError {
+    pub fn new(kind: ClassiErrorKind) -> Self {
+        Self { kind }
+    }
+
+    pub fn kind(&self) -> ClassiErrorKind {
+        self.kind
+    }
+}
+
+impl Display for ClassiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "classification error: {}", self.kind.as_str())
+    }
+}
+
+impl Error for ClassiError {}
+
+pub type Database = String;
+pub type Table = String;
+pub type Field = String;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Default, Hash, Deserialize)]
+pub struct FieldMeta(pub Database, pub Table, pub Field);
+
+impl FieldMeta {
+    /// 只保留`scope`范围内的部分，范围外的部分置空，用于按范围比较字段
+    pub fn scoped(&self, scope: MatchScope) -> FieldMeta {
+        match scope {
+            MatchScope::DbTableField => self.clone(),
+            MatchScope::DbTable => FieldMeta(self.0.clone(), self.1.clone(), String::new()),
+            MatchScope::Db => FieldMeta(self.0.clone(), String::new(), String::new()),
+        }
+    }
+
+    /// 在`scope`范围内两个字段是否相同
+    pub fn matches(&self, other: &FieldMeta, scope: MatchScope) -> bool {
+        match scope {
+            MatchScope::DbTableField => self == other,
+            MatchScope::DbTable => self.0 == other.0 && self.1 == other.1,
+            MatchScope::Db => self.0 == other.0,
+        }
+    }
+}
+
+/// 对比时字段身份的范围，范围外的部分不参与比较
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchScope {
+    /// 数据库名、表名和字段名都相同
+    #[default]
+    DbTableField,
+    /// 数据库名和表名相同即可，用于只分类到表的情况
+    DbTable,
+    /// 数据库名相同即可
+    Db,
+}
+
+impl FromStr for MatchScope {
+    type Err = SisyphusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "db-table-field" => Ok(MatchScope::DbTableField),
+            "db-table" => Ok(MatchScope::DbTable),
+            "db" => Ok(MatchScope::Db),
+            _ => Err(SisyphusError::InvalidOption(format!(
+                "unknown match scope [{}], expected db-table-field, db-table or db",
+                s
+            ))),
+        }
+    }
+}
+
+impl Display for FieldMeta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}-{}", self.0, self.1, self.2)
+    }
+}
+
+impl Serialize for FieldMeta {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut ser = serializer.serialize_tuple_struct("field", 3)?;
+        ser.serialize_field(&self.0)?;
+        ser.serialize_field(&self.1)?;
+        ser.serialize_field(&self.2)?;
+        ser.end()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ClassiVal {
+    Root,
+    Classi(String),
+    Field(FieldMeta),
+}
+
+impl Display for ClassiVal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClassiVal::Root => write!(f, "root"),
+            ClassiVal::Classi(ref s) => write!(f, "classi({})", s),
+            ClassiVal::Field(ref dtf) => write!(f, "field({})", dtf),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ClassiNode {
+    pub val: ClassiVal,
+    pub subs: Option<Vec<ClassiNode>>,
+}
+
+impl From<&ClassiVal> for ClassiNode {
+    fn from(value: &ClassiVal) -> Self {
+        let val = value.clone();
+        Self { val, subs: None }
+    }
+}
+
+impl ClassiNode {
+    pub fn new(val: ClassiVal) -> Self {
+        Self { val, subs: None }
+    }
+
+    pub fn find_node(&self, val: &ClassiVal) -> Option<&ClassiNode> {
+        if self.val == *val {
+            Some(self)
+        } else {
+            match self.subs {
+                Some(ref subs) => {
+                    for sub_node in subs {
+                        if let Some(n) = sub_node.find_node(val) {
+                            return Some(n);
+                        }
+                    }
+                    None
+                }
+                None => None,
+            }
+        }
+    }
+
+    /// 在直接下级中查找名称为`classi`的分类节点
+    pub fn find_child(&self, classi: &str) -> Option<&ClassiNode> {
+        self.subs
+            .as_ref()?
+            .iter()
+            .find(|sub| matches!(sub.val, ClassiVal::Classi(ref inner) if inner == classi))
+    }
+
+    /// 在直接下级中查找名称为`classi`的分类节点，不存在时创建
+    fn child_or_insert(&mut self, classi: &str) -> &mut ClassiNode {
+        let subs = self.subs.get_or_insert_with(Vec::new);
+        let pos = match subs
+            .iter()
+            .position(|sub| matches!(sub.val, ClassiVal::Classi(ref inner) if inner == classi))
+        {
+            Some(pos) => pos,
+            None => {
+                subs.push(ClassiNode::new(ClassiVal::Classi(classi.to_string())));
+                subs.len() - 1
+            }
+        };
+        &mut subs[pos]
+    }
+
+    /// 节点下（包括各级下级分类）的字段数量
+    fn field_count(&self) -> usize {
+        match self.val {
+            ClassiVal::Field(_) => 1,
+            _ => self
+                .subs
+                .iter()
+                .flatten()
+                .map(ClassiNode::field_count)
+                .sum(),
+        }
+    }
+
+    fn to_string(&self, space: usize) -> String {
+        const INDENT: &str = "  ";
+        let mut res = String::new();
+        match self.val {
+            ClassiVal::Root => {
+                if let Some(sub) = &self.subs {
+                    for e in sub {
+                        res.push_str(&e.to_string(space));
+                    }
+                }
+            }
+            ClassiVal::Classi(ref inner) => {
+                res.push_str((INDENT.repeat(space) + inner.as_str() + "\n").as_str());
+                if let Some(sub) = &self.subs {
+                    for e in sub {
+                        res.push_str(&e.to_string(space + 1));
+                    }
+                }
+            }
+            ClassiVal::Field(ref dtf) => {
+                res.push_str((INDENT.repeat(space) + dtf.to_string().as_str() + "\n").as_str());
+            }
+        }
+
+        res
+    }
+}
+
+#[derive(Clone)]
+pub struct ClassiTree {
+    root: ClassiNode,
+    /// 各分类级别的表头名称，如`一级分类`、`二级分类`
+    pub(crate) level_names: Vec<String>,
+    /// 字段到分类路径的索引，第一次按字段查询时生成，添加字段时清空
+    field_index: OnceLock<HashMap<FieldMeta, Vec<String>>>,
+    /// 所有字段及其分类路径的摘要，第一次对比时生成，添加字段时清空
+    leaf_digest: OnceLock<Vec<u8>>,
+}
+
+impl Default for ClassiTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClassiTree {
+    pub fn new() -> Self {
+        ClassiTree {
+            root: ClassiNode::new(ClassiVal::Root),
+            level_names: Vec::new(),
+            field_index: OnceLock::new(),
+            leaf_digest: OnceLock::new(),
+        }
+    }
+
+    /// 根据资源生成空的分类模版树，其中的数据库资源依次对应数据库、表、字段三级节点
+    ///
+    /// 数据库资源包含的资源视为表，表包含的资源视为字段，其它资源只会继续查找其包含的数据库资源
+    pub fn from_resource(resource: &Resource) -> ClassiTree {
+        let mut tree = ClassiTree::new();
+        tree.level_names = vec![String::from("数据库名称"), String::from("表名称")];
+        let mut dbs = Vec::new();
+        ClassiTree::_collect_databases(resource, &mut dbs);
+
+        let db_nodes = dbs
+            .into_iter()
+            .map(|db| {
+                let table_nodes = db
+                    .contains
+                    .iter()
+                    .flatten()
+                    .map(|tb| {
+                        let field_nodes = tb
+                            .contains
+                            .iter()
+                            .flatten()
+                            .map(|fd| {
+                                ClassiNode::new(ClassiVal::Field(FieldMeta(
+                                    db.name.clone(),
+                                    tb.name.clone(),
+                                    fd.name.clone(),
+                                )))
+                            })
+                            .collect::<Vec<_>>();
+                        ClassiNode {
+                            val: ClassiVal::Classi(tb.name.clone()),
+                            subs: Some(field_nodes).filter(|subs| !subs.is_empty()),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                ClassiNode {
+                    val: ClassiVal::Classi(db.name.clone()),
+                    subs: Some(table_nodes).filter(|subs| !subs.is_empty()),
+                }
+            })
+            .collect::<Vec<_>>();
+        tree.root.subs = Some(db_nodes).filter(|subs| !subs.is_empty());
+        tree
+    }
+
+    fn _collect_databases<'a>(resource: &'a Resource, dbs: &mut Vec<&'a Resource>) {
+        if resource.resource_type == ResourceType::DB_TYPE {
+            dbs.push(resource);
+        } else if let Some(ref contains) = resource.contains {
+            for sub in contains {
+                ClassiTree::_collect_databases(sub, dbs);
+            }
+        }
+    }
+
+    /// 分类级别的名称，没有表头名称时使用位置编号
+    pub fn level_name(&self, depth: usize) -> String {
+        match self.level_names.get(depth) {
+            Some(name) => name.clone(),
+            None => format!("level {}", depth),
+        }
+    }
+
+    /// 按节点的值查找，存在多个同名分类时返回深度优先遍历中最先找到的节点，
+    /// 需要区分不同上级下的同名分类时使用`find_path`
+    pub fn find_node(&self, val: &ClassiVal) -> Option<&ClassiNode> {
+        self.root.find_node(val)
+    }
+
+    /// 按分类路径逐级查找分类节点，不同上级下的同名分类不会混淆
+    pub fn find_path(&self, path: &[&str]) -> Option<&ClassiNode> {
+        if path.is_empty() {
+            return None;
+        }
+        path.iter()
+            .try_fold(&self.root, |node, classi| node.find_child(classi))
+    }
+
+    /// 取出分类路径对应的子树，新树只包含该分类节点及其下级，找不到路径时返回`None`
+    pub fn subtree(&self, path: &[&str]) -> Option<ClassiTree> {
+        let node = self.find_path(path)?;
+        let mut root = ClassiNode::new(ClassiVal::Root);
+        root.subs = Some(vec![node.clone()]);
+        Some(ClassiTree {
+            root,
+            level_names: self
+                .level_names
+                .iter()
+                .skip(path.len() - 1)
+                .cloned()
+                .collect(),
+            field_index: OnceLock::new(),
+            leaf_digest: OnceLock::new(),
+        })
+    }
+
+    /// 按分类路径添加字段，路径上不存在的分类节点依次创建
+    ///
+    /// 同一分类路径只对应一个节点，即最先创建的节点，之后路径相同的字段都添加到该节点下；
+    /// 不同上级分类下的同名分类是互不相关的节点
+    pub fn add_node(&mut self, classis: &[&str], field: FieldMeta) -> Result<(), ClassiError> {
+        if classis.is_empty() {
+            return Err(ClassiError::new(ClassiErrorKind::LevelsMissing));
+        }
+        self.field_index.take();
+        self.leaf_digest.take();
+
+        let mut node = &mut self.root;
+        for classi in classis {
+            node = node.child_or_insert(classi);
+        }
+        let field = ClassiVal::Field(field);
+        let subs = node.subs.get_or_insert_with(Vec::new);
+        if subs.iter().any(|sub| sub.val == field) {
+            return Err(ClassiError::new(ClassiErrorKind::NodeExists));
+        }
+        subs.push(ClassiNode::new(field));
+        Ok(())
+    }
+
+    pub fn all_leaves(&self) -> Vec<Vec<&ClassiNode>> {
+        let mut res = Vec::new();
+
+        let mut cur_q = Vec::<&ClassiNode>::new();
+        if let Some(ref subs) = self.root.subs {
+            for sub in subs {
+                ClassiTree::_collect_leave(sub, &mut cur_q, &mut res);
+                cur_q.clear();
+            }
+        }
+        res
+    }
+
+    fn _collect_leave<'a>(
+        node: &'a ClassiNode,
+        cur_q: &mut Vec<&'a ClassiNode>,
+        res: &mut Vec<Vec<&'a ClassiNode>>,
+    ) {
+        cur_q.push(node);
+        if let Some(ref subs) = node.subs {
+            for sub in subs {
+                ClassiTree::_collect_leave(sub, cur_q, res);
+            }
+            cur_q.pop();
+        } else {
+            res.push(cur_q.clone());
+            cur_q.pop();
+        }
+    }
+
+    /// 和另一棵分类结果树做对比，生成对比结果
+    ///
+    /// 每个字段生成一个对比结果，只有分类结果中相同分类路径下存在该字段时才算匹配
+    pub fn diff(&self, other: &ClassiTree) -> DiffResult {
+        self.diff_in_scope(other, MatchScope::default())
+    }
+
+    /// 按指定的范围对比，如`MatchScope::DbTable`时相同分类路径下有同一张表的任意字段即算匹配
+    ///
+    /// 两棵树的字段和分类路径完全相同时（如回归测试中的同一份文件），不再逐个字段查找，
+    /// 直接生成全部匹配的对比结果，和逐个对比的结果相同
+    pub fn diff_in_scope(&self, other: &ClassiTree, scope: MatchScope) -> DiffResult {
+        // 先比较字段数量，数量相同时才比较摘要，摘要只在第一次对比时计算
+        if self.root.field_count() == other.root.field_count()
+            && self.leaf_digest() == other.leaf_digest()
+        {
+            return self.identical_diff();
+        }
+        self.diff_iter_in_scope(other, scope).collect()
+    }
+
+    /// 所有字段及其分类路径的摘要，排序后再计算，和字段的顺序无关
+    fn leaf_digest(&self) -> &[u8] {
+        self.leaf_digest.get_or_init(|| self.compute_leaf_digest())
+    }
+
+    fn compute_leaf_digest(&self) -> Vec<u8> {
+        let mut leaves: Vec<String> = self
+            .field_paths()
+            .map(|(classis, field)| {
+                format!(
+                    "{}\0{}\0{}\0{}",
+                    classis.join("\x1f"),
+                    field.0,
+                    field.1,
+                    field.2
+                )
+            })
+            .collect();
+        leaves.sort_unstable();
+        let mut hasher = Sha256::new();
+        for leaf in &leaves {
+            hasher.update(leaf.as_bytes());
+            hasher.update(b"\n");
+        }
+        hasher.finalize().to_vec()
+    }
+
+    /// 和自身对比的结果，每个字段都完全匹配，顺序和`diff_iter`相同
+    fn identical_diff(&self) -> DiffResult {
+        self.field_paths()
+            .map(|(classis, field)| DiffUnit {
+                classis: classis.into_iter().map(String::from).collect(),
+                field: field.clone(),
+                field_exist: true,
+                match_kind: MatchKind::Exact,
+                missing_level: None,
+            })
+            .collect()
+    }
+
+    /// 忽略分类级别顺序的对比（实验性），字段在分类结果中的各级分类组成的集合和标准答案相同即算匹配
+    ///
+    /// 这种对比不再要求上下级关系一致，例如`a/b`和`b/a`视为相同的分类，会放宽分类体系的层级含义，
+    /// 只应在两边使用相同的分类但排列顺序不同时使用。字段本身仍须相同，未匹配时不给出`missing_level`
+    ///
+    /// 字段按`scope`的范围比较
+    pub fn diff_unordered(&self, other: &ClassiTree, scope: MatchScope) -> DiffResult {
+        let sorted = |mut classis: Vec<_>| {
+            classis.sort_unstable();
+            classis
+        };
+        let mut other_paths = HashMap::<FieldMeta, Vec<Vec<&str>>>::new();
+        for (classis, field) in other.field_paths() {
+            other_paths
+                .entry(field.scoped(scope))
+                .or_default()
+                .push(sorted(classis));
+        }
+
+        self.field_paths()
+            .map(|(classis, field)| {
+                let key = sorted(classis.clone());
+                let field_exist = other_paths
+                    .get(&field.scoped(scope))
+                    .is_some_and(|paths| paths.contains(&key));
+                DiffUnit {
+                    classis: classis.into_iter().map(String::from).collect(),
+                    field: field.clone(),
+                    field_exist,
+                    match_kind: if field_exist {
+                        MatchKind::Exact
+                    } else {
+                        MatchKind::Missing
+                    },
+                    missing_level: None,
+                }
+            })
+            .collect()
+    }
+
+    /// 逐个叶子节点和另一棵分类结果树做对比，对比结果在迭代时才生成
+    pub fn diff_iter<'a>(&'a self, other: &'a ClassiTree) -> impl Iterator<Item = DiffUnit> + 'a {
+        self.diff_iter_in_scope(other, MatchScope::default())
+    }
+
+    /// 按指定的范围逐个叶子节点对比，对比结果在迭代时才生成
+    pub fn diff_iter_in_scope<'a>(
+        &'a self,
+        other: &'a ClassiTree,
+        scope: MatchScope,
+    ) -> impl Iterator<Item = DiffUnit> + 'a {
+        self.all_leaves()
+            .into_iter()
+            .filter_map(move |path| ClassiTree::diff_leaf(&path, other, scope))
+    }
+
+    /// 对比一个叶子节点，叶子节点不是字段时（没有字段的分类）返回`None`
+    ///
+    /// 每个叶子节点单独对比，同一字段出现在多个分类路径下时（`ParseConfig::field_in_path`）
+    /// 每条路径各产生一个对比结果
+    fn diff_leaf(path: &[&ClassiNode], other: &ClassiTree, scope: MatchScope) -> Option<DiffUnit> {
+        let field = match path.last().map(|n| &n.val) {
+            Some(ClassiVal::Field(field)) => field,
+            _ => return None,
+        };
+        let classis: Vec<String> = path
+            .iter()
+            .filter_map(|n| match &n.val {
+                ClassiVal::Classi(classi) => Some(classi.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let mut node = Some(&other.root);
+        let mut missing_level = None;
+        for (depth, classi) in classis.iter().enumerate() {
+            node = node.and_then(|n| n.find_child(classi));
+            if node.is_none() {
+                missing_level = Some(depth);
+                break;
+            }
+        }
+        let field_exist = node.and_then(|n| n.subs.as_ref()).is_some_and(|subs| {
+            subs.iter()
+                .any(|sub| matches!(&sub.val, ClassiVal::Field(f) if f.matches(field, scope)))
+        });
+
+        Some(DiffUnit {
+            classis,
+            field: field.clone(),
+            field_exist,
+            match_kind: if field_exist {
+                MatchKind::Exact
+            } else {
+                MatchKind::Missing
+            },
+            missing_level,
+        })
+    }
+
+    /// 逐个遍历所有叶子节点上的字段
+    pub fn fields(&self) -> impl Iterator<Item = &FieldMeta> {
+        self.all_leaves()
+            .into_iter()
+            .filter_map(|path| match path.last().map(|n| &n.val) {
+                Some(ClassiVal::Field(field)) => Some(field),
+                _ => None,
+            })
+    }
+
+    /// 逐个遍历所有字段以及字段所在的分类路径
+    pub fn field_paths(&self) -> impl Iterator<Item = (Vec<&str>, &FieldMeta)> {
+        self.all_leaves().into_iter().filter_map(|path| {
+            let classis = path
+                .iter()
+                .filter_map(|n| match &n.val {
+                    ClassiVal::Classi(classi) => Some(classi.as_str()),
+                    _ => None,
+                })
+                .collect();
+            match path.last().map(|n| &n.val) {
+                Some(ClassiVal::Field(field)) => Some((classis, field)),
+                _ => None,
+            }
+        })
+    }
+
+    /// 每个字段在另一棵分类树中匹配的分类级数，即两边分类路径的最长公共前缀（最近公共祖先的深度）
+    ///
+    /// 另一棵树中没有该字段时为0，字段出现在多个分类路径下时使用最先找到的路径
+    pub fn matched_depths(&self, other: &ClassiTree) -> Vec<usize> {
+        let mut other_paths = HashMap::<&FieldMeta, Vec<&str>>::new();
+        for (classis, field) in other.field_paths() {
+            other_paths.entry(field).or_insert(classis);
+        }
+        self.field_paths()
+            .map(|(classis, field)| {
+                other_paths.get(field).map_or(0, |other| {
+                    classis
+                        .iter()
+                        .zip(other)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                })
+            })
+            .collect()
+    }
+
+    /// 下面没有任何字段的大类，如标准答案模版中的可选大类
+    pub fn classes_without_fields(&self) -> Vec<String> {
+        let with_fields: HashSet<&str> = self
+            .field_paths()
+            .filter_map(|(classis, _)| classis.first().copied())
+            .collect();
+        self.root
+            .subs
+            .iter()
+            .flatten()
+            .filter_map(|sub| match &sub.val {
+                ClassiVal::Classi(classi) if !with_fields.contains(classi.as_str()) => {
+                    Some(classi.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn field_index(&self) -> &HashMap<FieldMeta, Vec<String>> {
+        self.field_index.get_or_init(|| {
+            let mut index = HashMap::new();
+            for (classis, field) in self.field_paths() {
+                index
+                    .entry(field.clone())
+                    .or_insert_with(|| classis.into_iter().map(String::from).collect());
+            }
+            index
+        })
+    }
+
+    /// 分类树中是否有该字段，不考虑字段所在的分类
+    pub fn contains_field(&self, fm: &FieldMeta) -> bool {
+        self.field_index().contains_key(fm)
+    }
+
+    /// 字段所在的分类路径，字段出现在多个分类路径下时返回深度优先遍历中最先找到的路径
+    pub fn field_path(&self, fm: &FieldMeta) -> Option<&[String]> {
+        self.field_index().get(fm).map(Vec::as_slice)
+    }
+
+    /// 所有字段组成的集合，重复的字段只保留一个
+    pub fn field_set(&self) -> HashSet<FieldMeta> {
+        self.fields().cloned().collect()
+    }
+
+    /// 两棵分类树中都有的字段，字段按`scope`的范围比较，返回的字段只保留范围内的部分
+    pub fn common_fields(&self, other: &ClassiTree, scope: MatchScope) -> HashSet<FieldMeta> {
+        let other_fields: HashSet<FieldMeta> =
+            other.fields().map(|field| field.scoped(scope)).collect();
+        self.fields()
+            .map(|field| field.scoped(scope))
+            .filter(|field| other_fields.contains(field))
+            .collect()
+    }
+
+    /// 和另一棵分类树做字段对账，只比较字段是否存在，不考虑字段所在的分类
+    pub fn reconcile(&self, other: &ClassiTree) -> Reconciliation {
+        let ours = self.field_set();
+        let theirs = other.field_set();
+
+        let mut missing: Vec<FieldMeta> = ours.difference(&theirs).cloned().collect();
+        let mut extra: Vec<FieldMeta> = theirs.difference(&ours).cloned().collect();
+        missing.sort();
+        extra.sort();
+        Reconciliation { missing, extra }
+    }
+
+    /// 和另一棵分类树对比分类体系的结构，不考虑字段
+    pub fn taxonomy_diff(&self, other: &ClassiTree) -> TaxonomyDiff {
+        let ours = self.class_paths();
+        let theirs = other.class_paths();
+
+        let mut removed: Vec<String> = ours.difference(&theirs).cloned().collect();
+        let mut added: Vec<String> = theirs.difference(&ours).cloned().collect();
+        removed.sort();
+        added.sort();
+        TaxonomyDiff { added, removed }
+    }
+
+    /// 检查同名分类是否出现在不同的上级分类下
+    ///
+    /// 合法的分类体系中同一分类只有一个上级，这类冲突通常是分类结果的数据错误
+    pub fn hierarchy_conflicts(&self) -> Vec<HierarchyConflict> {
+        let mut parents = BTreeMap::<String, BTreeSet<String>>::new();
+        let mut path = Vec::new();
+        ClassiTree::_collect_parents(&self.root, &mut path, &mut parents);
+
+        parents
+            .into_iter()
+            .filter(|(_, sups)| sups.len() > 1)
+            .map(|(class, sups)| HierarchyConflict {
+                class,
+                parents: sups.into_iter().collect(),
+            })
+            .collect()
+    }
+
+    fn _collect_parents<'a>(
+        node: &'a ClassiNode,
+        path: &mut Vec<&'a str>,
+        parents: &mut BTreeMap<String, BTreeSet<String>>,
+    ) {
+        if let Some(ref subs) = node.subs {
+            for sub in subs {
+                if let ClassiVal::Classi(ref classi) = sub.val {
+                    parents
+                        .entry(classi.clone())
+                        .or_default()
+                        .insert(path.join("/"));
+                    path.push(classi);
+                    ClassiTree::_collect_parents(sub, path, parents);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    /// 所有分类节点的路径，以`/`连接各级分类名称
+    fn class_paths(&self) -> HashSet<String> {
+        let mut res = HashSet::new();
+        for path in self.all_leaves() {
+            let mut cur = Vec::new();
+            for node in path {
+                if let ClassiVal::Classi(ref classi) = node.val {
+                    cur.push(classi.as_str());
+                    res.insert(cur.join("/"));
+                }
+            }
+        }
+        res
+    }
+}
+
+impl Display for ClassiTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.root.to_string(0).trim())
+    }
+}
+
+/// 分类树序列化后的形式，每个分类下先列出下级分类再列出字段
+#[derive(Serialize, Deserialize)]
+struct TreeDoc {
+    #[serde(default)]
+    level_names: Vec<String>,
+    #[serde(default)]
+    classes: Vec<ClassDoc>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ClassDoc {
+    name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    classes: Vec<ClassDoc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<FieldMeta>,
+}
+
+impl From<&ClassiNode> for ClassDoc {
+    fn from(node: &ClassiNode) -> Self {
+        let mut doc = ClassDoc {
+            name: match &node.val {
+                ClassiVal::Classi(name) => name.clone(),
+                val => val.to_string(),
+            },
+            classes: Vec::new(),
+            fields: Vec::new(),
+        };
+        for sub in node.subs.iter().flatten() {
+            match &sub.val {
+                ClassiVal::Field(field) => doc.fields.push(field.clone()),
+                _ => doc.classes.push(ClassDoc::from(sub)),
+            }
+        }
+        doc
+    }
+}
+
+impl From<ClassDoc> for ClassiNode {
+    fn from(doc: ClassDoc) -> Self {
+        let subs: Vec<ClassiNode> = doc
+            .classes
+            .into_iter()
+            .map(ClassiNode::from)
+            .chain(
+                doc.fields
+                    .into_iter()
+                    .map(|field| ClassiNode::new(ClassiVal::Field(field))),
+            )
+            .collect();
+        ClassiNode {
+            val: ClassiVal::Classi(doc.name),
+            subs: Some(subs).filter(|subs| !subs.is_empty()),
+        }
+    }
+}
+
+impl Serialize for ClassiTree {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        TreeDoc {
+            level_names: self.level_names.clone(),
+            classes: self
+                .root
+                .subs
+                .iter()
+                .flatten()
+                .map(ClassDoc::from)
+                .collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ClassiTree {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let doc = TreeDoc::deserialize(deserializer)?;
+        let subs: Vec<ClassiNode> = doc.classes.into_iter().map(ClassiNode::from).collect();
+        Ok(ClassiTree {
+            root: ClassiNode {
+                val: ClassiVal::Root,
+                subs: Some(subs).filter(|subs| !subs.is_empty()),
+            },
+            level_names: doc.level_names,
+            field_index: OnceLock::new(),
+            leaf_digest: OnceLock::new(),
+        })
+    }
+}
+
+/// 字段的匹配方式
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MatchKind {
+    /// 完全匹配
+    Exact,
+    /// 分类结果把字段分到了标准答案分类路径的某个上级分类下，方向正确但不够具体，不算匹配
+    Partial,
+    /// 未匹配
+    #[default]
+    Missing,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DiffUnit {
+    pub classis: Vec<String>,
+    pub field: FieldMeta,
+    pub field_exist: bool,
+    pub match_kind: MatchKind,
+    /// 未匹配时，分类结果中第一个找不到的分类级别
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missing_level: Option<usize>,
+}
+
+pub type DiffResult = Vec<DiffUnit>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(db: &str, table: &str, field: &str) -> FieldMeta {
+        FieldMeta(db.into(), table.into(), field.into())
+    }
+
+    /// 分类路径对应节点的直接下级数量
+    fn children(tree: &ClassiTree, path: &[&str]) -> usize {
+        tree.find_path(path)
+            .and_then(|node| node.subs.as_ref())
+            .map_or(0, Vec::len)
+    }
+
+    #[test]
+    fn add_node_reuses_existing_classes() {
+        let mut tree = ClassiTree::new();
+        tree.add_node(&["个人信息", "身份信息"], field("crm", "user", "name"))
+            .unwrap();
+        tree.add_node(&["个人信息", "身份信息"], field("crm", "user", "id_card"))
+            .unwrap();
+        tree.add_node(&["个人信息", "联系方式"], field("crm", "user", "phone"))
+            .unwrap();
+
+        assert_eq!(tree.root.subs.as_ref().map(Vec::len), Some(1));
+        assert_eq!(children(&tree, &["个人信息"]), 2);
+        assert_eq!(children(&tree, &["个人信息", "身份信息"]), 2);
+        assert_eq!(tree.fields().count(), 3);
+    }
+
+    #[test]
+    fn add_node_rejects_duplicated_field() {
+        let mut tree = ClassiTree::new();
+        tree.add_node(&["个人信息"], field("crm", "user", "name"))
+            .unwrap();
+        let e = tree
+            .add_node(&["个人信息"], field("crm", "user", "name"))
+            .unwrap_err();
+        assert_eq!(e.kind(), ClassiErrorKind::NodeExists);
+        assert_eq!(tree.fields().count(), 1);
+
+        let e = tree.add_node(&[], field("crm", "user", "id")).unwrap_err();
+        assert_eq!(e.kind(), ClassiErrorKind::LevelsMissing);
+    }
+
+    #[test]
+    fn add_node_keeps_all_fields_under_shared_ancestors() {
+        let mut tree = ClassiTree::new();
+        let mut added = Vec::new();
+        for a in 0..4 {
+            for b in 0..4 {
+                for c in 0..4 {
+                    let path = [format!("a{}", a), format!("b{}", b), format!("c{}", c)];
+                    let path: Vec<&str> = path.iter().map(String::as_str).collect();
+                    for f in 0..5 {
+                        let fm = field("db", &format!("t{}{}{}", a, b, c), &format!("f{}", f));
+                        tree.add_node(&path, fm.clone()).unwrap();
+                        added.push((path.join("/"), fm));
+                    }
+                }
+            }
+        }
+
+        assert_eq!(tree.fields().count(), added.len());
+        assert_eq!(tree.root.subs.as_ref().map(Vec::len), Some(4));
+        assert_eq!(children(&tree, &["a1"]), 4);
+        assert_eq!(children(&tree, &["a1", "b2"]), 4);
+        assert_eq!(children(&tree, &["a1", "b2", "c3"]), 5);
+        for (path, fm) in &added {
+            assert_eq!(tree.field_path(fm).map(|p| p.join("/")), Some(path.clone()));
+        }
+    }
+
+    /// 三级分类，`身份信息`同时出现在两个大类下
+    fn sample() -> ClassiTree {
+        let mut tree = ClassiTree::new();
+        tree.level_names = vec!["一级分类".into(), "二级分类".into(), "三级分类".into()];
+        tree.add_node(
+            &["个人信息", "身份信息", "证件"],
+            field("crm", "user", "id_card"),
+        )
+        .unwrap();
+        tree.add_node(
+            &["个人信息", "身份信息", "姓名"],
+            field("crm", "user", "name"),
+        )
+        .unwrap();
+        tree.add_node(
+            &["个人信息", "联系方式", "电话"],
+            field("crm", "user", "phone"),
+        )
+        .unwrap();
+        tree.add_node(
+            &["企业信息", "身份信息", "证件"],
+            field("crm", "corp", "license"),
+        )
+        .unwrap();
+        tree
+    }
+
+    #[test]
+    fn subtree_extracts_mid_level_class() {
+        let tree = sample();
+        let sub = tree.subtree(&["个人信息", "身份信息"]).unwrap();
+
+        let mut fields: Vec<String> = sub.fields().map(|fm| fm.2.clone()).collect();
+        fields.sort();
+        assert_eq!(fields, ["id_card", "name"]);
+        assert_eq!(sub.level_name(0), "二级分类");
+        assert_eq!(sub.level_name(1), "三级分类");
+        assert_eq!(
+            sub.field_path(&field("crm", "user", "name")),
+            Some(&[String::from("身份信息"), String::from("姓名")][..])
+        );
+    }
+
+    #[test]
+    fn subtree_distinguishes_repeated_names() {
+        let tree = sample();
+        let corp = tree.subtree(&["企业信息", "身份信息"]).unwrap();
+        let fields: Vec<&FieldMeta> = corp.fields().collect();
+        assert_eq!(fields, [&field("crm", "corp", "license")]);
+        assert!(!corp.contains_field(&field("crm", "user", "id_card")));
+    }
+
+    #[test]
+    fn subtree_of_missing_path_is_none() {
+        let tree = sample();
+        assert!(tree.subtree(&["个人信息", "交易信息"]).is_none());
+        assert!(tree.subtree(&["身份信息"]).is_none());
+        assert!(tree.subtree(&[]).is_none());
+    }
+
+    #[test]
+    fn same_class_name_under_different_parents_stays_separate() {
+        let mut tree = ClassiTree::new();
+        tree.add_node(&["个人信息", "身份信息"], field("crm", "user", "id_card"))
+            .unwrap();
+        tree.add_node(&["企业信息", "身份信息"], field("crm", "corp", "license"))
+            .unwrap();
+        tree.add_node(&["个人信息", "身份信息"], field("crm", "user", "name"))
+            .unwrap();
+
+        assert_eq!(children(&tree, &["个人信息", "身份信息"]), 2);
+        assert_eq!(children(&tree, &["企业信息", "身份信息"]), 1);
+        assert_eq!(
+            tree.field_path(&field("crm", "corp", "license")),
+            Some(&[String::from("企业信息"), String::from("身份信息")][..])
+        );
+        assert_eq!(
+            tree.field_path(&field("crm", "user", "name")),
+            Some(&[String::from("个人信息"), String::from("身份信息")][..])
+        );
+    }
+
+    /// 各字段是否匹配，按字段名排列
+    fn matched(r: &DiffResult) -> Vec<(&str, bool)> {
+        let mut res: Vec<(&str, bool)> = r
+            .iter()
+            .map(|unit| (unit.field.2.as_str(), unit.field_exist))
+            .collect();
+        res.sort();
+        res
+    }
+
+    #[test]
+    fn diff_unordered_ignores_level_order() {
+        let mut solution = ClassiTree::new();
+        solution
+            .add_node(&["个人信息", "身份信息"], field("crm", "user", "name"))
+            .unwrap();
+        solution
+            .add_node(&["个人信息", "身份信息"], field("crm", "user", "id_card"))
+            .unwrap();
+        solution
+            .add_node(&["交易信息", "订单"], field("crm", "orders", "amount"))
+            .unwrap();
+        let mut answer = ClassiTree::new();
+        answer
+            .add_node(&["身份信息", "个人信息"], field("crm", "user", "name"))
+            .unwrap();
+        answer
+            .add_node(&["个人信息", "身份信息"], field("crm", "user", "id_card"))
+            .unwrap();
+        answer
+            .add_node(&["交易信息", "支付"], field("crm", "orders", "amount"))
+            .unwrap();
+
+        let ordered = solution.diff(&answer);
+        assert_eq!(
+            matched(&ordered),
+            [("amount", false), ("id_card", true), ("name", false)]
+        );
+
+        let unordered = solution.diff_unordered(&answer, MatchScope::DbTableField);
+        assert_eq!(
+            matched(&unordered),
+            [("amount", false), ("id_card", true), ("name", true)]
+        );
+        assert!(unordered.iter().all(|unit| unit.missing_level.is_none()));
+        assert!(unordered
+            .iter()
+            .all(|unit| (unit.match_kind == MatchKind::Exact) == unit.field_exist));
+
+        let mut renamed = ClassiTree::new();
+        renamed
+            .add_node(&["身份信息", "个人信息"], field("crm", "user", "other"))
+            .unwrap();
+        let by_table = solution.diff_unordered(&renamed, MatchScope::DbTable);
+        assert_eq!(
+            matched(&by_table),
+            [("amount", false), ("id_card", true), ("name", true)]
+        );
+    }
+
+    #[test]
+    fn field_lookup_follows_added_fields() {
+        let mut tree = sample();
+        let email = field("crm", "user", "email");
+        assert!(tree.contains_field(&field("crm", "user", "phone")));
+        assert!(!tree.contains_field(&email));
+        assert_eq!(tree.field_path(&email), None);
+
+        // 查询后生成的索引在添加字段时清空，之后的查询能找到新的字段
+        tree.add_node(&["个人信息", "联系方式", "邮箱"], email.clone())
+            .unwrap();
+        assert!(tree.contains_field(&email));
+        assert_eq!(
+            tree.field_path(&email).map(|path| path.join("/")),
+            Some(String::from("个人信息/联系方式/邮箱"))
+        );
+
+        // 同一字段出现在多个分类路径下时返回深度优先遍历中最先找到的路径
+        tree.add_node(&["企业信息", "联系方式"], email.clone())
+            .unwrap();
+        assert_eq!(
+            tree.field_path(&email).map(|path| path.join("/")),
+            Some(String::from("个人信息/联系方式/邮箱"))
+        );
+    }
+
+    #[test]
+    fn clone_is_independent_of_the_original() {
+        let mut deep = ClassiTree::new();
+        let path: Vec<String> = (0..64).map(|i| format!("L{}", i)).collect();
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        deep.add_node(&path, field("db", "tb", "deep")).unwrap();
+        let tree = sample();
+        let before = tree.to_string();
+        assert!(tree.contains_field(&field("crm", "user", "name")));
+
+        let mut copy = tree.clone();
+        let deep_copy = deep.clone();
+        assert_eq!(copy.to_string(), before);
+        assert_eq!(deep_copy.to_string(), deep.to_string());
+
+        copy.add_node(
+            &["个人信息", "身份信息", "姓名"],
+            field("crm", "user", "alias"),
+        )
+        .unwrap();
+        copy.add_node(&["企业信息", "联系方式"], field("crm", "corp", "phone"))
+            .unwrap();
+        copy.level_names[0] = String::from("大类");
+
+        assert_eq!(tree.to_string(), before);
+        assert_eq!(tree.fields().count(), 4);
+        assert_eq!(copy.fields().count(), 6);
+        assert_eq!(tree.level_name(0), "一级分类");
+        assert!(!tree.contains_field(&field("crm", "user", "alias")));
+        assert!(copy.contains_field(&field("crm", "user", "alias")));
+    }
+
+    #[test]
+    fn display_indents_classes_and_fields() {
+        let mut tree = ClassiTree::new();
+        tree.add_node(&["PII", "个人信息", "id"], field("crm", "user", "id_card"))
+            .unwrap();
+        tree.add_node(&["PII", "mix混合"], field("crm", "user", "phone"))
+            .unwrap();
+        tree.add_node(&["交易信息"], field("crm", "orders", "amount"))
+            .unwrap();
+        assert_eq!(
+            tree.to_string(),
+            "PII\n  个人信息\n    id\n      crm-user-id_card\n  mix混合\n    crm-user-phone\n\
+             交易信息\n  crm-orders-amount"
+        );
+    }
+
+    #[test]
+    fn identical_trees_take_the_fast_path() {
+        let diff_json = |diff: DiffResult| serde_json::to_string(&diff).unwrap();
+        let solution = sample();
+        let mut answer = ClassiTree::new();
+        let mut paths: Vec<_> = solution.field_paths().collect();
+        paths.reverse();
+        for (classis, fm) in paths {
+            answer.add_node(&classis, fm.clone()).unwrap();
+        }
+
+        for scope in [MatchScope::DbTableField, MatchScope::DbTable] {
+            let fast = solution.diff_in_scope(&answer, scope);
+            assert!(solution.leaf_digest.get().is_some() && answer.leaf_digest.get().is_some());
+            assert!(fast.iter().all(|unit| unit.match_kind == MatchKind::Exact));
+            let slow = solution.diff_iter_in_scope(&answer, scope).collect();
+            assert_eq!(diff_json(fast), diff_json(slow));
+        }
+
+        // 添加字段后摘要失效，字段数量不同时不再计算摘要
+        answer
+            .add_node(
+                &["企业信息", "联系方式", "电话"],
+                field("crm", "corp", "phone"),
+            )
+            .unwrap();
+        assert!(answer.leaf_digest.get().is_none());
+        let diff = solution.diff(&answer);
+        assert!(answer.leaf_digest.get().is_none());
+        assert_eq!(
+            diff_json(diff),
+            diff_json(solution.diff_iter(&answer).collect())
+        );
+    }
+}
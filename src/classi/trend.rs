@@ -0,0 +1,117 @@
+//! 多个不同时间的分类结果的成绩趋势
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use super::{
+    reader::{read_classi_result, ParseConfig},
+    report::{score, ScoringReport},
+    tree::ClassiTree,
+};
+
+/// 某个时间点的分类成绩
+#[derive(Serialize, Debug)]
+pub struct TrendPoint {
+    pub date: NaiveDate,
+    pub file: String,
+    #[serde(flatten)]
+    pub report: ScoringReport,
+}
+
+/// 时间序列CSV中的一行，`scope`为`overall`时表示总的正确率
+#[derive(Serialize)]
+struct TrendRow<'a> {
+    date: NaiveDate,
+    file: &'a str,
+    scope: &'a str,
+    class: &'a str,
+    total: i32,
+    matched: i32,
+    accuracy: f64,
+}
+
+/// 从文件名中推断日期，支持`2024-06-01`和`20240601`两种形式
+fn infer_date(file: &Path) -> Option<NaiveDate> {
+    let stem = file.file_stem()?.to_string_lossy();
+    for (start, _) in stem.char_indices() {
+        let rest = &stem[start..];
+        for (fmt, len) in [("%Y-%m-%d", 10), ("%Y%m%d", 8)] {
+            if let Some(candidate) = rest.get(..len) {
+                if let Ok(date) = NaiveDate::parse_from_str(candidate, fmt) {
+                    return Some(date);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// 解析`file=date`形式的参数，没有指定日期时从文件名中推断
+pub fn parse_dated_answer(spec: &str) -> anyhow::Result<(PathBuf, NaiveDate)> {
+    if let Some((file, date)) = spec.rsplit_once('=') {
+        if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            return Ok((PathBuf::from(file), date));
+        }
+    }
+    let file = PathBuf::from(spec);
+    let date = infer_date(&file)
+        .with_context(|| format!("failed to infer the date of the answer file [{}]", spec))?;
+    Ok((file, date))
+}
+
+/// 对多个不同时间的分类结果打分，按时间排序后输出各大类正确率的时间序列
+pub fn trend_report(
+    solution: &ClassiTree,
+    answers: &[(PathBuf, NaiveDate)],
+    answer_config: &ParseConfig,
+    format: &str,
+) -> anyhow::Result<()> {
+    let mut points = Vec::with_capacity(answers.len());
+    for (file, date) in answers {
+        let answer = read_classi_result(file, false, answer_config)?;
+        points.push(TrendPoint {
+            date: *date,
+            file: file.to_string_lossy().into_owned(),
+            report: score(solution, &answer),
+        });
+    }
+    points.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.file.cmp(&b.file)));
+
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&points)?),
+        _ => {
+            let mut wtr = csv::Writer::from_writer(io::stdout());
+            for point in &points {
+                wtr.serialize(TrendRow {
+                    date: point.date,
+                    file: &point.file,
+                    scope: "overall",
+                    class: "",
+                    total: point.report.total,
+                    matched: point.report.matched,
+                    accuracy: point.report.overall,
+                })?;
+                for class in &point.report.per_class {
+                    wtr.serialize(TrendRow {
+                        date: point.date,
+                        file: &point.file,
+                        scope: "class",
+                        class: &class.class,
+                        total: class.total,
+                        matched: class.matched,
+                        accuracy: class.accuracy,
+                    })?;
+                }
+            }
+            wtr.flush()?;
+        }
+    }
+
+    Ok(())
+}
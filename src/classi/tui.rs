@@ -0,0 +1,177 @@
+//! 浏览对比结果的终端界面
+//!
+//! 左侧为各大类的正确率，右侧为所选大类下未匹配的字段，按`/`输入过滤条件
+
+use std::io;
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+use super::{
+    report::ScoringReport,
+    tree::{DiffResult, DiffUnit},
+};
+
+struct App<'a> {
+    report: &'a ScoringReport,
+    diff: &'a DiffResult,
+    /// 第0项为全部大类，之后为`report.per_class`中的各个大类
+    classes: ListState,
+    filter: String,
+    editing: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(report: &'a ScoringReport, diff: &'a DiffResult) -> Self {
+        let mut classes = ListState::default();
+        classes.select(Some(0));
+        Self {
+            report,
+            diff,
+            classes,
+            filter: String::new(),
+            editing: false,
+        }
+    }
+
+    fn selected_class(&self) -> Option<&str> {
+        match self.classes.selected() {
+            Some(0) | None => None,
+            Some(i) => self.report.per_class.get(i - 1).map(|c| c.class.as_str()),
+        }
+    }
+
+    fn misses(&self) -> Vec<&DiffUnit> {
+        let class = self.selected_class();
+        self.diff
+            .iter()
+            .filter(|unit| !unit.field_exist)
+            .filter(|unit| {
+                class.is_none_or(|c| unit.classis.first().map(String::as_str) == Some(c))
+            })
+            .filter(|unit| unit.field.to_string().contains(self.filter.as_str()))
+            .collect()
+    }
+
+    fn move_selection(&mut self, forward: bool) {
+        let len = self.report.per_class.len() + 1;
+        let cur = self.classes.selected().unwrap_or(0);
+        let next = if forward {
+            (cur + 1) % len
+        } else {
+            (cur + len - 1) % len
+        };
+        self.classes.select(Some(next));
+    }
+}
+
+fn draw(f: &mut Frame, app: &mut App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(f.size());
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(rows[0]);
+
+    let mut class_items = vec![ListItem::new(format!(
+        "全部  {:.2}% ({}/{})",
+        app.report.overall * 100f64,
+        app.report.matched,
+        app.report.total
+    ))];
+    class_items.extend(app.report.per_class.iter().map(|c| {
+        ListItem::new(format!(
+            "{}  {:.2}% ({}/{})",
+            c.class,
+            c.accuracy * 100f64,
+            c.matched,
+            c.total
+        ))
+    }));
+    let class_list = List::new(class_items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("classification"),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_stateful_widget(class_list, panes[0], &mut app.classes);
+
+    let misses = app.misses();
+    let miss_items: Vec<ListItem> = misses
+        .iter()
+        .map(|unit| ListItem::new(format!("{}  {}", unit.field, unit.classis.join("/"))))
+        .collect();
+    let miss_list = List::new(miss_items).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("missing fields ({})", misses.len())),
+    );
+    f.render_widget(miss_list, panes[1]);
+
+    let status_line = if app.editing {
+        format!("filter: {}_", app.filter)
+    } else {
+        format!(
+            "filter: {}    ↑/↓ select  / filter  esc clear  q quit",
+            app.filter
+        )
+    };
+    f.render_widget(Paragraph::new(status_line), rows[1]);
+}
+
+/// 启动终端界面，按`q`退出
+pub fn run(report: &ScoringReport, diff: &DiffResult) -> anyhow::Result<()> {
+    enable_raw_mode()?;
+    io::stdout().execute(EnterAlternateScreen)?;
+    let res = event_loop(report, diff);
+    io::stdout().execute(LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    res
+}
+
+fn event_loop(report: &ScoringReport, diff: &DiffResult) -> anyhow::Result<()> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let mut app = App::new(report, diff);
+    loop {
+        terminal.draw(|f| draw(f, &mut app))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if app.editing {
+            match key.code {
+                KeyCode::Enter => app.editing = false,
+                KeyCode::Esc => {
+                    app.editing = false;
+                    app.filter.clear();
+                }
+                KeyCode::Backspace => {
+                    app.filter.pop();
+                }
+                KeyCode::Char(c) => app.filter.push(c),
+                _ => (),
+            }
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') => return Ok(()),
+            KeyCode::Up | KeyCode::Char('k') => app.move_selection(false),
+            KeyCode::Down | KeyCode::Char('j') => app.move_selection(true),
+            KeyCode::Char('/') => app.editing = true,
+            KeyCode::Esc => app.filter.clear(),
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! 将分类树输出为分类结果格式的表格
+
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use calamine::{Data, DataType, Range};
+use rust_xlsxwriter::Workbook;
+
+use super::{reader::DB_HEADER, tree::ClassiTree};
+
+/// 分类树对应的表格，第一行为表头，之后每行一个字段，按行的内容排序
+///
+/// 分类路径较短的字段在后面补空的分类列，没有分类级别名称时表头为`class1`、`class2`等
+pub fn tree_table(tree: &ClassiTree) -> Vec<Vec<String>> {
+    let levels = tree
+        .field_paths()
+        .map(|(classis, _)| classis.len())
+        .max()
+        .unwrap_or(0);
+    let mut header: Vec<String> = (0..levels)
+        .map(|depth| {
+            tree.level_names
+                .get(depth)
+                .cloned()
+                .unwrap_or_else(|| format!("class{}", depth + 1))
+        })
+        .collect();
+    header.extend([DB_HEADER, "表名称", "字段名称"].map(String::from));
+
+    let mut rows: Vec<Vec<String>> = tree
+        .field_paths()
+        .map(|(classis, field)| {
+            let mut row: Vec<String> = classis.into_iter().map(String::from).collect();
+            row.resize(levels, String::new());
+            row.extend([field.0.clone(), field.1.clone(), field.2.clone()]);
+            row
+        })
+        .collect();
+    rows.sort();
+
+    let mut table = vec![header];
+    table.extend(rows);
+    table
+}
+
+/// 将表格写入文件，按扩展名输出为`.csv`或者`.xlsx`，`.xlsx`的工作表名称为`sheet_name`
+pub fn write_table(table: &[Vec<String>], path: &Path, sheet_name: &str) -> anyhow::Result<()> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => {
+            let mut wtr = csv::Writer::from_path(path)?;
+            for row in table {
+                wtr.write_record(row)?;
+            }
+            wtr.flush()?;
+        }
+        Some("xlsx") => {
+            let mut workbook = Workbook::new();
+            let worksheet = workbook.add_worksheet();
+            worksheet.set_name(sheet_name)?;
+            for (i, row) in table.iter().enumerate() {
+                for (j, cell) in row.iter().enumerate() {
+                    worksheet.write_string(i as u32, j as u16, cell)?;
+                }
+            }
+            workbook.save(path)?;
+        }
+        _ => {
+            return Err(anyhow::Error::msg(format!(
+                "unsupported output file [{}], expected .csv or .xlsx",
+                path.to_string_lossy()
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// 对比原工作表和规范化后的表格，返回内容有变化的行数和去掉的行数
+///
+/// 原工作表第一行视为表头，完全为空的行不计入；规范化后的某行和原工作表的任何一行都不相同时视为有变化
+pub fn count_changes(sheet: &Range<Data>, table: &[Vec<String>]) -> (usize, usize) {
+    let raw: Vec<Vec<String>> = sheet
+        .rows()
+        .skip(1)
+        .filter(|row| row.iter().any(|cell| !cell.is_empty()))
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+    let raw_set: HashSet<&Vec<String>> = raw.iter().collect();
+
+    let rows = table.get(1..).unwrap_or_default();
+    let changed = rows.iter().filter(|row| !raw_set.contains(row)).count();
+    (changed, raw.len().saturating_sub(rows.len()))
+}
+
+/// 原子地写入文件
+///
+/// 先写入同一目录下的临时文件，完整写入并落盘后再重命名为`path`，
+/// 中途失败时`path`保持原样，不会留下不完整的文件
+pub fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(format!(".tmp-{}", std::process::id()));
+    let tmp_file = PathBuf::from(tmp_name);
+    let res = fs::File::create(&tmp_file)
+        .and_then(|mut f| {
+            f.write_all(content)?;
+            f.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp_file, path));
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_file);
+    }
+    res
+}
@@ -0,0 +1,138 @@
+//! 库对外的错误类型
+//!
+//! 读取分类结果、加解密以及解析选项值（如`Compression`的`FromStr`）的公开函数返回`SisyphusError`，调用方可以按错误的种类分别处理，
+//! 如密钥不对和工作簿损坏；内部仍使用`anyhow`附加上下文，在公开函数的边界转为`SisyphusError`
+
+use std::{error::Error, fmt::Display, io};
+
+use calamine::XlsxError;
+
+use crate::classi::tree::{ClassiError, ClassiErrorKind};
+
+pub type Result<T> = std::result::Result<T, SisyphusError>;
+
+/// 库中各种不同的错误
+#[derive(Debug)]
+pub enum SisyphusError {
+    /// 读写文件失败
+    Io(io::Error),
+    /// 密钥不对或者加密文件被篡改，无法解密
+    WrongKey,
+    /// 指定的密钥格式不对，如长度不是32字节或者不是合法的十六进制
+    InvalidKey(String),
+    /// 加密文件的格式不对，或者格式版本不支持
+    MalformedEncryptedFile(String),
+    /// 无法解析的Excel或者CSV文件，包括受密码保护的工作簿
+    MalformedWorkbook(String),
+    /// 工作簿中找不到指定的工作表
+    SheetNotFound(String),
+    /// 分类结果的内容不符合要求，如找不到表头、字段重复，`message`包含出错的位置
+    InvalidResult {
+        kind: ClassiErrorKind,
+        message: String,
+    },
+    /// 资源的`id`与已有的资源重复
+    DuplicateResourceId(u64),
+    /// 文件的格式版本不支持，通常是更新版本的程序生成的
+    UnsupportedVersion(u64),
+    /// 无法解析的选项值，如未知的压缩方式、对比范围或者规范化方式
+    InvalidOption(String),
+    /// 其他错误
+    Other(String),
+}
+
+impl SisyphusError {
+    /// 分类结果内容错误的种类，其他错误返回`None`
+    pub fn classi_kind(&self) -> Option<ClassiErrorKind> {
+        match self {
+            SisyphusError::InvalidResult { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SisyphusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SisyphusError::Io(e) => write!(f, "io error: {}", e),
+            SisyphusError::WrongKey => write!(
+                f,
+                "failed to decrypt: the key does not match or the file is corrupted"
+            ),
+            SisyphusError::InvalidKey(msg) => write!(f, "invalid key: {}", msg),
+            SisyphusError::MalformedEncryptedFile(msg) => {
+                write!(f, "malformed encrypted file: {}", msg)
+            }
+            SisyphusError::MalformedWorkbook(msg) => write!(f, "malformed workbook: {}", msg),
+            SisyphusError::SheetNotFound(name) => write!(f, "the sheet [{}] was not found", name),
+            SisyphusError::InvalidResult { message, .. } => write!(f, "{}", message),
+            SisyphusError::DuplicateResourceId(id) => {
+                write!(f, "the resource id {} already exists", id)
+            }
+            SisyphusError::UnsupportedVersion(version) => {
+                write!(f, "unsupported file version {}", version)
+            }
+            SisyphusError::InvalidOption(msg) => write!(f, "{}", msg),
+            SisyphusError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl Error for SisyphusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SisyphusError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SisyphusError {
+    fn from(e: io::Error) -> Self {
+        SisyphusError::Io(e)
+    }
+}
+
+impl From<ClassiError> for SisyphusError {
+    fn from(e: ClassiError) -> Self {
+        SisyphusError::InvalidResult {
+            kind: e.kind(),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// 按错误链中最具体的错误归类，上下文保留在错误信息中
+impl From<anyhow::Error> for SisyphusError {
+    fn from(e: anyhow::Error) -> Self {
+        let message = format!("{:#}", e);
+        if let Some(classi) = e.downcast_ref::<ClassiError>() {
+            return SisyphusError::InvalidResult {
+                kind: classi.kind(),
+                message,
+            };
+        }
+        if let Some(XlsxError::WorksheetNotFound(name)) = e.downcast_ref::<XlsxError>() {
+            return SisyphusError::SheetNotFound(name.clone());
+        }
+        if e.downcast_ref::<XlsxError>().is_some() || e.downcast_ref::<csv::Error>().is_some() {
+            return SisyphusError::MalformedWorkbook(message);
+        }
+        match e.downcast::<SisyphusError>() {
+            // 带有说明的错误使用完整的错误链作为说明，保留外层附加的上下文
+            Ok(SisyphusError::InvalidResult { kind, .. }) => {
+                SisyphusError::InvalidResult { kind, message }
+            }
+            Ok(SisyphusError::MalformedWorkbook(_)) => SisyphusError::MalformedWorkbook(message),
+            Ok(SisyphusError::MalformedEncryptedFile(_)) => {
+                SisyphusError::MalformedEncryptedFile(message)
+            }
+            Ok(SisyphusError::Other(_)) => SisyphusError::Other(message),
+            Ok(e) => e,
+            Err(e) => match e.downcast::<io::Error>() {
+                Ok(e) => SisyphusError::Io(e),
+                Err(_) => SisyphusError::Other(message),
+            },
+        }
+    }
+}
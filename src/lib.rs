@@ -0,0 +1,10 @@
+//! 靶场应用
+//!
+//! `classi`为数据分类探针的核心逻辑，`cls`程序在此之上提供命令行入口，
+//! `resource`为靶场中资源的定义，`error`为库对外的错误类型
+
+pub mod classi;
+pub mod error;
+pub mod resource;
+
+pub use error::SisyphusError;
@@ -0,0 +1,523 @@
+//! 靶场中的资源，定义见README的资源的定义一节
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::Write,
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, SisyphusError};
+
+/// 场景文件当前的格式版本
+///
+/// 第1版的资源没有`deleted`和`delete_datetime`，读取时按未删除补齐
+pub const SCENE_VERSION: u64 = 2;
+
+#[derive(Serialize, Deserialize)]
+pub struct Resource {
+    pub id: u64,
+    pub name: String,
+    pub description: String,
+    pub resource_type: ResourceType,
+    pub resource_form: ResourceForm,
+    pub level: u8,
+    pub contains: Option<Vec<Resource>>,
+    pub status: ResourceStatus,
+    pub create_datetime: DateTime<Local>,
+    pub last_update_datetime: DateTime<Local>,
+    pub deleted: bool,
+    pub delete_datetime: Option<DateTime<Local>>,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResourceStatus {
+    CREATED,
+    DEPLOYED,
+    PREPARED,
+    USING,
+    EXCEPTION,
+    REVOKING,
+    UNAVAILABLE,
+    DELETED,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResourceType {
+    OS_TYPE,
+    DB_TYPE,
+    APP_TYPE,
+    PROFILER_TYPE,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum ResourceForm {
+    Single,
+    Composed,
+}
+
+/// 资源的集合，保证所有资源（包括组合资源中包含的资源）的`id`互不相同
+#[derive(Default)]
+pub struct ResourceStore {
+    resources: Vec<Resource>,
+    ids: HashSet<u64>,
+    next_id: u64,
+}
+
+impl ResourceStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 由已有的资源创建，存在重复的`id`时返回错误
+    pub fn from_resources(resources: Vec<Resource>) -> Result<Self> {
+        let mut store = Self::new();
+        for resource in resources {
+            store.insert(resource)?;
+        }
+        Ok(store)
+    }
+
+    /// 从JSON格式的资源列表加载，存在重复的`id`时返回错误
+    pub fn from_json(json: &str) -> Result<Self> {
+        let resources: Vec<Resource> = serde_json::from_str(json)
+            .map_err(|e| SisyphusError::Other(format!("failed to parse resources: {}", e)))?;
+        Self::from_resources(resources)
+    }
+
+    /// 分配下一个`id`，分配的`id`单调递增并且大于已有的所有`id`
+    pub fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// 添加新的资源，为它以及它包含的资源分配`id`，返回它的`id`
+    pub fn create(&mut self, mut resource: Resource) -> u64 {
+        self._assign_ids(&mut resource);
+        let id = resource.id;
+        self.resources.push(resource);
+        id
+    }
+
+    /// 添加已经有`id`的资源，它或者它包含的资源的`id`已经存在时返回错误，不做任何修改
+    pub fn insert(&mut self, resource: Resource) -> Result<()> {
+        let mut ids = Vec::new();
+        _collect_ids(std::slice::from_ref(&resource), &mut ids);
+        let mut seen = HashSet::new();
+        if let Some(id) = ids
+            .iter()
+            .find(|id| self.ids.contains(*id) || !seen.insert(**id))
+        {
+            return Err(SisyphusError::DuplicateResourceId(*id));
+        }
+        if let Some(max) = ids.iter().max() {
+            self.next_id = self.next_id.max(max + 1);
+        }
+        self.ids.extend(ids);
+        self.resources.push(resource);
+        Ok(())
+    }
+
+    /// 按`id`查找资源，包括组合资源中包含的资源
+    pub fn find_by_id(&self, id: u64) -> Option<&Resource> {
+        if !self.ids.contains(&id) {
+            return None;
+        }
+        _find_by_id(&self.resources, id)
+    }
+
+    pub fn resources(&self) -> &[Resource] {
+        &self.resources
+    }
+
+    fn _assign_ids(&mut self, resource: &mut Resource) {
+        resource.id = self.next_id();
+        self.ids.insert(resource.id);
+        for contained in resource.contains.iter_mut().flatten() {
+            self._assign_ids(contained);
+        }
+    }
+}
+
+fn _collect_ids(resources: &[Resource], res: &mut Vec<u64>) {
+    for resource in resources {
+        res.push(resource.id);
+        if let Some(contains) = &resource.contains {
+            _collect_ids(contains, res);
+        }
+    }
+}
+
+fn _find_by_id(resources: &[Resource], id: u64) -> Option<&Resource> {
+    resources.iter().find_map(|resource| {
+        if resource.id == id {
+            Some(resource)
+        } else {
+            _find_by_id(resource.contains.as_deref().unwrap_or_default(), id)
+        }
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Scene {
+    pub resources: Option<Vec<Resource>>,
+}
+
+/// 场景文件的内容，`version`为格式版本
+#[derive(Serialize)]
+struct SceneFile<'a> {
+    version: u64,
+    scene: &'a Scene,
+}
+
+impl Scene {
+    /// 序列化为当前版本的场景文件
+    pub fn to_json(&self) -> Result<String> {
+        let file = SceneFile {
+            version: SCENE_VERSION,
+            scene: self,
+        };
+        serde_json::to_string_pretty(&file)
+            .map_err(|e| SisyphusError::Other(format!("failed to serialize the scene: {}", e)))
+    }
+
+    /// 读取场景文件，旧版本的内容先迁移到当前版本，不认识的版本（如更新的程序生成的）返回错误
+    pub fn from_json(json: &str) -> Result<Scene> {
+        let parse_error = |e: serde_json::Error| {
+            SisyphusError::Other(format!("failed to parse the scene file: {}", e))
+        };
+        let mut file: serde_json::Value = serde_json::from_str(json).map_err(parse_error)?;
+        let version = file
+            .get("version")
+            .and_then(serde_json::Value::as_u64)
+            .ok_or_else(|| SisyphusError::Other(String::from("the scene file has no version")))?;
+        let scene = file
+            .get_mut("scene")
+            .map(serde_json::Value::take)
+            .ok_or_else(|| SisyphusError::Other(String::from("the scene file has no scene")))?;
+        let scene = match version {
+            1 => _migrate_v1(scene),
+            SCENE_VERSION => scene,
+            _ => return Err(SisyphusError::UnsupportedVersion(version)),
+        };
+        serde_json::from_value(scene).map_err(parse_error)
+    }
+
+    /// 对比两个场景中的资源，`self`为旧的场景
+    pub fn diff(&self, other: &Scene) -> ResourceDiff {
+        diff_resources(
+            self.resources.as_deref().unwrap_or_default(),
+            other.resources.as_deref().unwrap_or_default(),
+        )
+    }
+}
+
+/// 第1版到第2版：为每个资源（包括组合资源中包含的资源）补齐`deleted`和`delete_datetime`
+fn _migrate_v1(mut scene: serde_json::Value) -> serde_json::Value {
+    if let Some(resources) = scene
+        .get_mut("resources")
+        .and_then(serde_json::Value::as_array_mut)
+    {
+        resources.iter_mut().for_each(_migrate_resource_v1);
+    }
+    scene
+}
+
+fn _migrate_resource_v1(resource: &mut serde_json::Value) {
+    if let Some(resource) = resource.as_object_mut() {
+        resource
+            .entry("deleted")
+            .or_insert(serde_json::Value::Bool(false));
+        resource
+            .entry("delete_datetime")
+            .or_insert(serde_json::Value::Null);
+        if let Some(contains) = resource
+            .get_mut("contains")
+            .and_then(serde_json::Value::as_array_mut)
+        {
+            contains.iter_mut().for_each(_migrate_resource_v1);
+        }
+    }
+}
+
+/// 展开后的一个资源，不包含`contains`，通过`parent_id`关联上级资源
+#[derive(Serialize)]
+struct FlatResource<'a> {
+    id: u64,
+    /// 上级组合资源的`id`，顶级资源为`None`
+    parent_id: Option<u64>,
+    /// 在资源层级中的深度，顶级资源为0
+    depth: usize,
+    name: &'a str,
+    description: &'a str,
+    resource_type: &'a ResourceType,
+    resource_form: &'a ResourceForm,
+    level: u8,
+    status: ResourceStatus,
+    create_datetime: DateTime<Local>,
+    last_update_datetime: DateTime<Local>,
+    deleted: bool,
+    delete_datetime: Option<DateTime<Local>>,
+}
+
+/// 将资源以及组合资源中包含的资源逐个输出为一行JSON（JSON Lines），上级资源在下级资源之前，
+/// 返回输出的资源数量
+///
+/// `include_deleted`为假时跳过已删除的资源及其包含的资源，为真时输出全部资源，可以作为完整的审计记录
+pub fn write_json_lines<W: Write>(
+    resources: &[Resource],
+    include_deleted: bool,
+    mut w: W,
+) -> Result<usize> {
+    let mut count = 0;
+    let mut stack: Vec<(&Resource, Option<u64>, usize)> =
+        resources.iter().rev().map(|r| (r, None, 0)).collect();
+    while let Some((resource, parent_id, depth)) = stack.pop() {
+        if resource.deleted && !include_deleted {
+            continue;
+        }
+        let flat = FlatResource {
+            id: resource.id,
+            parent_id,
+            depth,
+            name: &resource.name,
+            description: &resource.description,
+            resource_type: &resource.resource_type,
+            resource_form: &resource.resource_form,
+            level: resource.level,
+            status: resource.status,
+            create_datetime: resource.create_datetime,
+            last_update_datetime: resource.last_update_datetime,
+            deleted: resource.deleted,
+            delete_datetime: resource.delete_datetime,
+        };
+        serde_json::to_writer(&mut w, &flat)
+            .map_err(|e| SisyphusError::Other(format!("failed to serialize a resource: {}", e)))?;
+        writeln!(w)?;
+        count += 1;
+        stack.extend(
+            resource
+                .contains
+                .iter()
+                .flatten()
+                .rev()
+                .map(|sub| (sub, Some(resource.id), depth + 1)),
+        );
+    }
+    w.flush()?;
+    Ok(count)
+}
+
+/// 两次资源快照的差异，资源按`id`对应，各列表按`id`排列
+#[derive(Debug, Default)]
+pub struct ResourceDiff {
+    /// 新快照中有而旧快照中没有的资源
+    pub added: Vec<u64>,
+    /// 旧快照中有而新快照中没有的资源
+    pub removed: Vec<u64>,
+    /// 状态发生变化的资源，依次为`id`、旧状态、新状态
+    pub status_changed: Vec<(u64, ResourceStatus, ResourceStatus)>,
+}
+
+/// 对比两次资源快照，组合资源会递归对比其中包含的资源
+pub fn diff_resources(old: &[Resource], new: &[Resource]) -> ResourceDiff {
+    let mut old_by_id = BTreeMap::new();
+    _collect_by_id(old, &mut old_by_id);
+    let mut new_by_id = BTreeMap::new();
+    _collect_by_id(new, &mut new_by_id);
+
+    let mut res = ResourceDiff::default();
+    for (id, old_res) in &old_by_id {
+        match new_by_id.get(id) {
+            None => res.removed.push(*id),
+            Some(new_res) if new_res.status != old_res.status => {
+                res.status_changed
+                    .push((*id, old_res.status, new_res.status))
+            }
+            Some(_) => (),
+        }
+    }
+    res.added = new_by_id
+        .keys()
+        .filter(|id| !old_by_id.contains_key(id))
+        .copied()
+        .collect();
+    res
+}
+
+fn _collect_by_id<'a>(resources: &'a [Resource], res: &mut BTreeMap<u64, &'a Resource>) {
+    for resource in resources {
+        res.insert(resource.id, resource);
+        if let Some(contains) = &resource.contains {
+            _collect_by_id(contains, res);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource(id: u64, status: ResourceStatus, contains: Vec<Resource>) -> Resource {
+        let now = Local::now();
+        Resource {
+            id,
+            name: format!("resource-{}", id),
+            description: String::new(),
+            resource_type: ResourceType::DB_TYPE,
+            resource_form: if contains.is_empty() {
+                ResourceForm::Single
+            } else {
+                ResourceForm::Composed
+            },
+            level: 0,
+            contains: Some(contains).filter(|contains| !contains.is_empty()),
+            status,
+            create_datetime: now,
+            last_update_datetime: now,
+            deleted: false,
+            delete_datetime: None,
+        }
+    }
+
+    /// 组合资源1包含2，2包含3和4
+    fn composed(child_status: ResourceStatus, leaf: u64) -> Vec<Resource> {
+        vec![resource(
+            1,
+            ResourceStatus::USING,
+            vec![resource(
+                2,
+                ResourceStatus::USING,
+                vec![
+                    resource(3, child_status, Vec::new()),
+                    resource(leaf, ResourceStatus::USING, Vec::new()),
+                ],
+            )],
+        )]
+    }
+
+    #[test]
+    fn diff_recurses_into_composed_resources() {
+        let old = Scene {
+            resources: Some(composed(ResourceStatus::USING, 4)),
+        };
+        let new = Scene {
+            resources: Some(composed(ResourceStatus::EXCEPTION, 5)),
+        };
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.status_changed,
+            [(3, ResourceStatus::USING, ResourceStatus::EXCEPTION)]
+        );
+        assert_eq!(diff.removed, [4]);
+        assert_eq!(diff.added, [5]);
+
+        let same = old.diff(&old);
+        assert!(same.added.is_empty() && same.removed.is_empty());
+        assert!(same.status_changed.is_empty());
+    }
+
+    #[test]
+    fn store_rejects_duplicated_ids_from_json() {
+        let json = |resources: &[Resource]| serde_json::to_string(resources).unwrap();
+
+        let top_level = [
+            resource(1, ResourceStatus::USING, Vec::new()),
+            resource(1, ResourceStatus::USING, Vec::new()),
+        ];
+        assert!(matches!(
+            ResourceStore::from_json(&json(&top_level)),
+            Err(SisyphusError::DuplicateResourceId(1))
+        ));
+
+        let mut nested = composed(ResourceStatus::USING, 3);
+        assert!(matches!(
+            ResourceStore::from_json(&json(&nested)),
+            Err(SisyphusError::DuplicateResourceId(3))
+        ));
+        nested.push(resource(4, ResourceStatus::USING, Vec::new()));
+        assert!(matches!(
+            ResourceStore::from_json(&json(&nested)),
+            Err(SisyphusError::DuplicateResourceId(3))
+        ));
+
+        let store = ResourceStore::from_json(&json(&composed(ResourceStatus::USING, 4))).unwrap();
+        assert_eq!(store.resources().len(), 1);
+        assert_eq!(store.find_by_id(4).unwrap().name, "resource-4");
+    }
+
+    #[test]
+    fn next_id_is_monotonic() {
+        let mut store = ResourceStore::from_resources(composed(ResourceStatus::USING, 4)).unwrap();
+        let first = store.next_id();
+        assert!(first > 4);
+        assert_eq!(store.next_id(), first + 1);
+
+        let id = store.create(resource(0, ResourceStatus::CREATED, Vec::new()));
+        assert_eq!(id, first + 2);
+        store
+            .insert(resource(100, ResourceStatus::CREATED, Vec::new()))
+            .unwrap();
+        assert_eq!(store.next_id(), 101);
+        store
+            .insert(resource(50, ResourceStatus::CREATED, Vec::new()))
+            .unwrap();
+        assert_eq!(store.next_id(), 102);
+    }
+
+    #[test]
+    fn scene_v1_is_migrated_to_the_current_version() {
+        let scene = Scene {
+            resources: Some(composed(ResourceStatus::USING, 4)),
+        };
+        let mut file: serde_json::Value = serde_json::from_str(&scene.to_json().unwrap()).unwrap();
+        assert_eq!(file["version"], SCENE_VERSION);
+
+        // 第1版的资源没有`deleted`和`delete_datetime`
+        fn strip(resource: &mut serde_json::Value) {
+            let resource = resource.as_object_mut().unwrap();
+            resource.remove("deleted");
+            resource.remove("delete_datetime");
+            if let Some(contains) = resource["contains"].as_array_mut() {
+                contains.iter_mut().for_each(strip);
+            }
+        }
+        file["version"] = serde_json::Value::from(1);
+        file["scene"]["resources"]
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .for_each(strip);
+        assert!(file["scene"]["resources"][0]["contains"][0]
+            .get("deleted")
+            .is_none());
+
+        let migrated = Scene::from_json(&file.to_string()).unwrap();
+        let resources = migrated.resources.as_deref().unwrap();
+        let mut ids = Vec::new();
+        _collect_ids(resources, &mut ids);
+        assert_eq!(ids, [1, 2, 3, 4]);
+        for id in ids {
+            let resource = _find_by_id(resources, id).unwrap();
+            assert!(!resource.deleted);
+            assert!(resource.delete_datetime.is_none());
+        }
+        assert!(scene.diff(&migrated).status_changed.is_empty());
+    }
+
+    #[test]
+    fn scene_of_unknown_version_is_rejected() {
+        let scene = Scene { resources: None };
+        let mut file: serde_json::Value = serde_json::from_str(&scene.to_json().unwrap()).unwrap();
+        file["version"] = serde_json::Value::from(SCENE_VERSION + 1);
+        assert!(matches!(
+            Scene::from_json(&file.to_string()),
+            Err(SisyphusError::UnsupportedVersion(v)) if v == SCENE_VERSION + 1
+        ));
+        assert!(Scene::from_json(&scene.to_json().unwrap()).is_ok());
+    }
+}
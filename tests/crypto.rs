@@ -0,0 +1,76 @@
+//! 使用指定的密钥加解密
+
+use std::{error::Error, fs};
+
+use sisyphus::{
+    classi::crypto::{
+        decrypt_bytes, decrypt_file, encrypt_bytes, encrypt_file, Compression, EncKey,
+    },
+    SisyphusError,
+};
+
+const PLAIN: &[u8] = b"db,table,field\ncrm,user,name\n";
+
+#[test]
+fn round_trip_with_custom_key() -> Result<(), Box<dyn Error>> {
+    let key = EncKey::new([42; 32]);
+    let enc = encrypt_bytes(PLAIN, &key)?;
+    assert_eq!(decrypt_bytes(&enc, &key)?, PLAIN);
+    Ok(())
+}
+
+#[test]
+fn round_trip_file_with_custom_key() -> Result<(), Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("sisyphus-crypto-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let ori_file = dir.join("plain.csv");
+    let enc_file = dir.join("plain_e");
+    fs::write(&ori_file, PLAIN)?;
+
+    let key = EncKey::from_hex(&"0f".repeat(32))?;
+    encrypt_file(&ori_file, &enc_file, Compression::Zstd, &key)?;
+    assert_eq!(decrypt_file(&enc_file, &key)?, PLAIN);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn wrong_key_fails() -> Result<(), Box<dyn Error>> {
+    let enc = encrypt_bytes(PLAIN, &EncKey::new([1; 32]))?;
+    for key in [EncKey::new([2; 32]), EncKey::built_in()] {
+        assert!(matches!(
+            decrypt_bytes(&enc, &key),
+            Err(SisyphusError::WrongKey)
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn key_file_must_be_32_bytes() -> Result<(), Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("sisyphus-key-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let key_file = dir.join("key");
+
+    fs::write(&key_file, [9u8; 31])?;
+    assert!(matches!(
+        EncKey::from_file(&key_file),
+        Err(SisyphusError::InvalidKey(_))
+    ));
+    fs::write(&key_file, [9u8; 32])?;
+    assert_eq!(
+        EncKey::from_file(&key_file)?.fingerprint(),
+        EncKey::new([9; 32]).fingerprint()
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn hex_key_is_validated() {
+    assert!(EncKey::from_hex("abcd").is_err());
+    assert!(EncKey::from_hex(&"zz".repeat(32)).is_err());
+    assert!(EncKey::from_hex(&format!(" {}\n", "AB".repeat(32))).is_ok());
+}
@@ -0,0 +1,118 @@
+//! 端到端的打分流程：生成标准答案和分类结果的工作簿，加密标准答案，解密读取后对比并计算成绩
+//!
+//! 使用合成的数据，不依赖任何真实的行业数据
+
+use std::{error::Error, fs, path::PathBuf};
+
+use rust_xlsxwriter::Workbook;
+use sisyphus::classi::{
+    crypto::Compression, decrypt_file, encrypt_file, read_classi_result,
+    read_classi_result_from_bytes, score, EncKey, InputFormat, ParseConfig,
+};
+
+const HEADER: [&str; 5] = ["一级分类", "二级分类", "数据库名称", "表名", "字段名"];
+
+/// 标准答案，两个大类共5个字段
+const SOLUTION: [[&str; 5]; 5] = [
+    ["个人信息", "身份信息", "crm", "user", "name"],
+    ["个人信息", "身份信息", "crm", "user", "id_card"],
+    ["个人信息", "联系方式", "crm", "user", "phone"],
+    ["交易信息", "订单", "crm", "orders", "order_no"],
+    ["交易信息", "订单", "crm", "orders", "amount"],
+];
+
+/// 分类结果，`phone`分错了大类，`amount`没有分类
+const ANSWER: [[&str; 5]; 4] = [
+    ["个人信息", "身份信息", "crm", "user", "name"],
+    ["个人信息", "身份信息", "crm", "user", "id_card"],
+    ["交易信息", "订单", "crm", "user", "phone"],
+    ["交易信息", "订单", "crm", "orders", "order_no"],
+];
+
+/// 测试用的临时目录，每个测试使用不同的名称，互不影响
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sisyphus-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// 按分类结果的格式写入工作簿，工作表名称为默认的`Sheet 1`
+fn write_workbook(path: &PathBuf, rows: &[[&str; 5]]) -> Result<(), Box<dyn Error>> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name("Sheet 1")?;
+    for (j, head) in HEADER.iter().enumerate() {
+        worksheet.write_string(0, j as u16, *head)?;
+    }
+    for (i, row) in rows.iter().enumerate() {
+        for (j, cell) in row.iter().enumerate() {
+            worksheet.write_string(i as u32 + 1, j as u16, *cell)?;
+        }
+    }
+    workbook.save(path)?;
+    Ok(())
+}
+
+#[test]
+fn score_encrypted_solution() -> Result<(), Box<dyn Error>> {
+    let dir = temp_dir("score-e2e");
+    let solution_file = dir.join("solution.xlsx");
+    let enc_file = dir.join("solution_e");
+    let answer_file = dir.join("answer.xlsx");
+    write_workbook(&solution_file, &SOLUTION)?;
+    write_workbook(&answer_file, &ANSWER)?;
+
+    let key = EncKey::new([7; 32]);
+    encrypt_file(&solution_file, &enc_file, Compression::Gzip, &key)?;
+    assert_eq!(decrypt_file(&enc_file, &key)?, fs::read(&solution_file)?);
+
+    let config = ParseConfig::builder().key(key).build();
+    let solution = read_classi_result(&enc_file, true, &config)?;
+    let answer = read_classi_result(&answer_file, false, &config)?;
+    assert_eq!(solution.level_name(0), "一级分类");
+    assert_eq!(solution.level_name(1), "二级分类");
+
+    let diff = solution.diff(&answer);
+    assert_eq!(diff.len(), SOLUTION.len());
+    let missed: Vec<&str> = diff
+        .iter()
+        .filter(|unit| !unit.field_exist)
+        .map(|unit| unit.field.2.as_str())
+        .collect();
+    assert_eq!(missed.len(), 2);
+    assert!(missed.contains(&"phone"));
+    assert!(missed.contains(&"amount"));
+
+    let report = score(&solution, &answer);
+    assert_eq!((report.total, report.matched), (5, 3));
+    assert!((report.overall - 0.6).abs() < 1e-9);
+
+    let classes: Vec<(&str, i32, i32)> = report
+        .per_class
+        .iter()
+        .map(|class| (class.class.as_str(), class.total, class.matched))
+        .collect();
+    assert_eq!(classes, [("个人信息", 3, 2), ("交易信息", 2, 1)]);
+    let personal = &report.per_class[0];
+    assert!((personal.accuracy - 2f64 / 3f64).abs() < 1e-9);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn score_plain_bytes_matches_file() -> Result<(), Box<dyn Error>> {
+    let dir = temp_dir("score-bytes");
+    let solution_file = dir.join("solution.xlsx");
+    write_workbook(&solution_file, &SOLUTION)?;
+
+    let config = ParseConfig::default();
+    let from_file = read_classi_result(&solution_file, false, &config)?;
+    let from_bytes =
+        read_classi_result_from_bytes(&fs::read(&solution_file)?, InputFormat::Xlsx, &config)?;
+    let report = score(&from_file, &from_bytes);
+    assert_eq!((report.total, report.matched), (5, 5));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}